@@ -0,0 +1,8 @@
+#!/bin/sh
+# Refreshes the bundled copy of the chip-8-database (https://github.com/chip-8/chip-8-database).
+set -e
+cd "$(dirname "$0")"
+url=https://raw.githubusercontent.com/chip-8/chip-8-database/master/database
+for file in programs.json sha1-hashes.json; do
+    curl -fsSL -o "$file" "$url/$file"
+done
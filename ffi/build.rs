@@ -0,0 +1,15 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+fn main() {
+    let dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=src/lib.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+
+    let config = cbindgen::Config::from_file(Path::new(&dir).join("cbindgen.toml"))
+        .expect("Unable to read cbindgen.toml");
+    cbindgen::generate_with_config(&dir, config)
+        .expect("Unable to generate the C header")
+        .write_to_file(out_dir.join("chip8.h"));
+}
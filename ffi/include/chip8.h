@@ -0,0 +1,166 @@
+#ifndef CHIP8_H
+#define CHIP8_H
+
+/* Generated by cbindgen from ffi/src/lib.rs, do not edit. */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Display width in pixels.
+ */
+#define CHIP8_WIDTH 64
+
+/**
+ * Display height in pixels.
+ */
+#define CHIP8_HEIGHT 32
+
+/**
+ * Size of the buffers passed to `chip8_save_state` and `chip8_load_state`.
+ */
+#define CHIP8_STATE_SIZE 6199
+
+/**
+ * An emulator instance; opaque to C.
+ */
+typedef struct Chip8 Chip8;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates an emulator with no ROM loaded, running 10 instructions per frame.
+ * `seed` drives CXNN. Free it with `chip8_free`.
+ */
+struct Chip8 *chip8_new(uint64_t seed);
+
+/**
+ * # Safety
+ *
+ * `chip8` must come from `chip8_new` and not be used afterwards; NULL is ignored.
+ */
+void chip8_free(struct Chip8 *chip8);
+
+/**
+ * Loads `len` bytes of ROM and resets. Returns 0, or -1 if the ROM is empty or
+ * too large, in which case the previous ROM stays loaded.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle and `rom` point to `len` readable bytes.
+ */
+int32_t chip8_load_rom(struct Chip8 *chip8, const uint8_t *rom, size_t len);
+
+/**
+ * Restarts the loaded ROM.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+void chip8_reset(struct Chip8 *chip8);
+
+/**
+ * Sets how many instructions `chip8_step_frame` runs.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+void chip8_set_tickrate(struct Chip8 *chip8, uint32_t tickrate);
+
+/**
+ * Runs `cycles` instructions without touching the timers. Returns 0, or -1 once the
+ * ROM runs an instruction the machine cannot execute; it stays halted until reset.
+ * Returns -2 if the emulator panicked, after which the handle should be reset.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+int32_t chip8_step_cycles(struct Chip8 *chip8, uint32_t cycles);
+
+/**
+ * Runs one 60 Hz frame: the tickrate's worth of instructions, then a timer tick.
+ * Returns 0, -1 or -2 like `chip8_step_cycles`.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+int32_t chip8_step_frame(struct Chip8 *chip8);
+
+/**
+ * Presses keypad key `key` (0 to 15); takes effect at the next step.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+void chip8_key_down(struct Chip8 *chip8, uint8_t key);
+
+/**
+ * Releases keypad key `key` (0 to 15); takes effect at the next step.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+void chip8_key_up(struct Chip8 *chip8, uint8_t key);
+
+/**
+ * The display, `CHIP8_WIDTH * CHIP8_HEIGHT` bytes of 0 or 1, row by row. The
+ * pointer stays valid for the life of the handle.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+const uint8_t *chip8_framebuffer(const struct Chip8 *chip8);
+
+/**
+ * Registers V0 to VF, 16 bytes. The pointer stays valid for the life of the handle.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+const uint8_t *chip8_registers(const struct Chip8 *chip8);
+
+/**
+ * Whether the beeper should sound.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle.
+ */
+bool chip8_sound(const struct Chip8 *chip8);
+
+/**
+ * Writes the machine and random source state to `out`. Returns 0, or -1 if `len`
+ * is not `CHIP8_STATE_SIZE`.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle and `out` point to `len` writable bytes.
+ */
+int32_t chip8_save_state(const struct Chip8 *chip8, uint8_t *out, size_t len);
+
+/**
+ * Restores a state written by `chip8_save_state`. Returns 0, or -1 if it is not a
+ * valid state, in which case nothing changes.
+ *
+ * # Safety
+ *
+ * `chip8` must be a live handle and `state` point to `len` readable bytes.
+ */
+int32_t chip8_load_state(struct Chip8 *chip8, const uint8_t *state, size_t len);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CHIP8_H */
@@ -0,0 +1,204 @@
+//! C bindings for the `chip_8` machine, built as `libchip8.so` / `libchip8.a`.
+//!
+//! The header for C is checked in as `include/chip8.h`. The build script regenerates it
+//! into cargo's `OUT_DIR` and `tests/header.rs` fails while the two differ; after
+//! changing the API, run `cbindgen --config cbindgen.toml --output include/chip8.h` in
+//! `ffi/`.
+//! A `Chip8` handle owns a machine, the loaded ROM and a random source; all functions
+//! take the handle returned by `chip8_new` and must not be called from two threads at once.
+
+use std::panic::{self, AssertUnwindSafe};
+use std::slice;
+
+use chip_8::emulator::{Emulator, SNAPSHOT_SIZE};
+use chip_8::machine::{self, Quirks};
+
+/// Display width in pixels.
+pub const CHIP8_WIDTH: usize = 64;
+/// Display height in pixels.
+pub const CHIP8_HEIGHT: usize = 32;
+/// Size of the buffers passed to `chip8_save_state` and `chip8_load_state`.
+pub const CHIP8_STATE_SIZE: usize = 6199;
+
+const _: () = assert!(CHIP8_WIDTH == machine::WIDTH && CHIP8_HEIGHT == machine::HEIGHT);
+const _: () = assert!(CHIP8_STATE_SIZE == SNAPSHOT_SIZE);
+
+/// An emulator instance; opaque to C.
+pub struct Chip8 {
+    emulator: Emulator,
+}
+
+/// Runs `step`, turning a panic into -2 instead of unwinding into C.
+fn catch_panic(step: impl FnOnce() -> i32) -> i32 {
+    panic::catch_unwind(AssertUnwindSafe(step)).unwrap_or(-2)
+}
+
+/// Creates an emulator with no ROM loaded, running 10 instructions per frame.
+/// `seed` drives CXNN. Free it with `chip8_free`.
+#[no_mangle]
+pub extern "C" fn chip8_new(seed: u64) -> *mut Chip8 {
+    Box::into_raw(Box::new(Chip8 {
+        emulator: Emulator::new(Quirks::default(), seed),
+    }))
+}
+
+/// # Safety
+///
+/// `chip8` must come from `chip8_new` and not be used afterwards; NULL is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_free(chip8: *mut Chip8) {
+    if !chip8.is_null() {
+        drop(Box::from_raw(chip8));
+    }
+}
+
+/// Loads `len` bytes of ROM and resets. Returns 0, or -1 if the ROM is empty or
+/// too large, in which case the previous ROM stays loaded.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle and `rom` point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_rom(chip8: *mut Chip8, rom: *const u8, len: usize) -> i32 {
+    if rom.is_null() {
+        return -1;
+    }
+    match (*chip8).emulator.load_rom(slice::from_raw_parts(rom, len)) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// Restarts the loaded ROM.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_reset(chip8: *mut Chip8) {
+    (*chip8).emulator.reset();
+}
+
+/// Sets how many instructions `chip8_step_frame` runs.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_set_tickrate(chip8: *mut Chip8, tickrate: u32) {
+    (*chip8).emulator.tickrate = tickrate;
+}
+
+/// Runs `cycles` instructions without touching the timers. Returns 0, or -1 once the
+/// ROM runs an instruction the machine cannot execute; it stays halted until reset.
+/// Returns -2 if the emulator panicked, after which the handle should be reset.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step_cycles(chip8: *mut Chip8, cycles: u32) -> i32 {
+    let emulator = &mut (*chip8).emulator;
+    catch_panic(|| match emulator.step_cycles(cycles) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    })
+}
+
+/// Runs one 60 Hz frame: the tickrate's worth of instructions, then a timer tick.
+/// Returns 0, -1 or -2 like `chip8_step_cycles`.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_step_frame(chip8: *mut Chip8) -> i32 {
+    let emulator = &mut (*chip8).emulator;
+    catch_panic(|| match emulator.step_frame() {
+        Ok(()) => 0,
+        Err(_) => -1,
+    })
+}
+
+/// Presses keypad key `key` (0 to 15); takes effect at the next step.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_key_down(chip8: *mut Chip8, key: u8) {
+    (*chip8).emulator.set_key(key, true);
+}
+
+/// Releases keypad key `key` (0 to 15); takes effect at the next step.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_key_up(chip8: *mut Chip8, key: u8) {
+    (*chip8).emulator.set_key(key, false);
+}
+
+/// The display, `CHIP8_WIDTH * CHIP8_HEIGHT` bytes of 0 or 1, row by row. The
+/// pointer stays valid for the life of the handle.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_framebuffer(chip8: *const Chip8) -> *const u8 {
+    (*chip8).emulator.machine().display.as_ptr()
+}
+
+/// Registers V0 to VF, 16 bytes. The pointer stays valid for the life of the handle.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_registers(chip8: *const Chip8) -> *const u8 {
+    (*chip8).emulator.machine().registers.as_ptr()
+}
+
+/// Whether the beeper should sound.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_sound(chip8: *const Chip8) -> bool {
+    (*chip8).emulator.machine().sound_on()
+}
+
+/// Writes the machine and random source state to `out`. Returns 0, or -1 if `len`
+/// is not `CHIP8_STATE_SIZE`.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle and `out` point to `len` writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_save_state(chip8: *const Chip8, out: *mut u8, len: usize) -> i32 {
+    if out.is_null() || len != CHIP8_STATE_SIZE {
+        return -1;
+    }
+    slice::from_raw_parts_mut(out, len).copy_from_slice(&(*chip8).emulator.snapshot());
+    0
+}
+
+/// Restores a state written by `chip8_save_state`. Returns 0, or -1 if it is not a
+/// valid state, in which case nothing changes.
+///
+/// # Safety
+///
+/// `chip8` must be a live handle and `state` point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn chip8_load_state(chip8: *mut Chip8, state: *const u8, len: usize) -> i32 {
+    if state.is_null() {
+        return -1;
+    }
+    match (*chip8).emulator.restore(slice::from_raw_parts(state, len)) {
+        Ok(()) => 0,
+        Err(_) => -1,
+    }
+}
@@ -0,0 +1,55 @@
+/* Exercises the C API; built and run by tests/c_api.rs. Exits non-zero on failure. */
+
+#include <stdio.h>
+#include <stdlib.h>
+
+#include "chip8.h"
+
+#define CHECK(condition)                                                  \
+    do {                                                                  \
+        if (!(condition)) {                                               \
+            fprintf(stderr, "%s:%d: check failed: %s\n", __FILE__,        \
+                    __LINE__, #condition);                                \
+            exit(1);                                                      \
+        }                                                                 \
+    } while (0)
+
+int main(void) {
+    /* I = 0x20A, draw one row at (V0, V0), wait for a key into V1, loop. */
+    static const uint8_t rom[] = {0xA2, 0x0A, 0xD0, 0x01, 0xF1, 0x0A,
+                                  0x12, 0x06, 0x00, 0x00, 0xF0};
+    static uint8_t state[CHIP8_STATE_SIZE];
+
+    Chip8 *chip8 = chip8_new(1);
+    CHECK(chip8 != NULL);
+    CHECK(chip8_load_rom(chip8, rom, 0) == -1);
+    CHECK(chip8_load_rom(chip8, rom, sizeof rom) == 0);
+
+    CHECK(chip8_step_cycles(chip8, 2) == 0);
+    const uint8_t *pixels = chip8_framebuffer(chip8);
+    CHECK(pixels[0] == 1 && pixels[3] == 1 && pixels[4] == 0);
+    CHECK(pixels[CHIP8_WIDTH] == 0);
+
+    CHECK(chip8_save_state(chip8, state, sizeof state - 1) == -1);
+    CHECK(chip8_save_state(chip8, state, sizeof state) == 0);
+
+    /* Press and release a key while FX0A waits for one. */
+    chip8_key_down(chip8, 0x7);
+    CHECK(chip8_step_frame(chip8) == 0);
+    chip8_key_up(chip8, 0x7);
+    CHECK(chip8_step_frame(chip8) == 0);
+    CHECK(chip8_registers(chip8)[1] == 0x7);
+    chip8_reset(chip8);
+    CHECK(chip8_framebuffer(chip8)[0] == 0);
+
+    CHECK(chip8_load_state(chip8, state, sizeof state) == 0);
+    CHECK(chip8_framebuffer(chip8)[0] == 1);
+    state[0] ^= 0xFF;
+    CHECK(chip8_load_state(chip8, state, sizeof state) == -1);
+    CHECK(!chip8_sound(chip8));
+
+    chip8_free(chip8);
+    chip8_free(NULL);
+    puts("ok");
+    return 0;
+}
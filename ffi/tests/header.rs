@@ -0,0 +1,16 @@
+//! Keeps the checked in `include/chip8.h` in step with what cbindgen generates.
+
+use std::fs;
+use std::path::Path;
+
+#[test]
+fn checked_in_header_is_up_to_date() {
+    let generated = fs::read_to_string(Path::new(env!("OUT_DIR")).join("chip8.h")).unwrap();
+    let checked_in =
+        fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("include/chip8.h")).unwrap();
+    assert!(
+        generated == checked_in,
+        "include/chip8.h is out of date; run `cbindgen --config cbindgen.toml --output \
+         include/chip8.h` in ffi/"
+    );
+}
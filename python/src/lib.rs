@@ -0,0 +1,201 @@
+//! The `chip8` Python module; `maturin develop` in this directory installs it.
+//!
+//! ```python
+//! import chip8, numpy as np
+//! emulator = chip8.Chip8(seed=1)
+//! emulator.load_rom(open("pong.ch8", "rb").read())
+//! emulator.press(0x1)
+//! emulator.step_frame(60)
+//! pixels = np.frombuffer(emulator.framebuffer(), np.uint8).reshape(chip8.HEIGHT, chip8.WIDTH)
+//! ```
+
+use pyo3::exceptions::{PyIndexError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+use chip_8::emulator::Emulator;
+use chip_8::machine::{self, Quirks};
+
+/// A CHIP-8 running without a screen, stepped from Python.
+#[pyclass]
+struct Chip8 {
+    emulator: Emulator,
+}
+
+fn check_key(key: u8) -> PyResult<u8> {
+    if key < 16 {
+        Ok(key)
+    } else {
+        Err(PyValueError::new_err(format!(
+            "no key {}, keys go from 0 to 15",
+            key
+        )))
+    }
+}
+
+/// `address..address + length` if it lies within memory.
+fn memory_range(address: usize, length: usize) -> PyResult<std::ops::Range<usize>> {
+    match address.checked_add(length) {
+        Some(end) if end <= machine::MEMORY_SIZE => Ok(address..end),
+        _ => Err(PyIndexError::new_err(format!(
+            "{} bytes from {:#x} run past the end of memory at {:#x}",
+            length,
+            address,
+            machine::MEMORY_SIZE
+        ))),
+    }
+}
+
+fn fault_error(fault: machine::Fault) -> PyErr {
+    PyRuntimeError::new_err(fault.to_string())
+}
+
+#[pymethods]
+impl Chip8 {
+    /// `seed` drives CXNN, so equal seeds and inputs replay a game exactly.
+    #[new]
+    #[pyo3(signature = (seed=0, tickrate=10))]
+    fn new(seed: u64, tickrate: u32) -> Self {
+        let mut emulator = Emulator::new(Quirks::default(), seed);
+        emulator.tickrate = tickrate;
+        Chip8 { emulator }
+    }
+
+    /// Instructions per frame.
+    #[getter]
+    fn tickrate(&self) -> u32 {
+        self.emulator.tickrate
+    }
+
+    #[setter]
+    fn set_tickrate(&mut self, tickrate: u32) {
+        self.emulator.tickrate = tickrate;
+    }
+
+    /// Held keys as a mask, bit N = key N; handed to the machine at the next step.
+    #[getter]
+    fn keys(&self) -> u16 {
+        self.emulator.keys()
+    }
+
+    #[setter]
+    fn set_keys(&mut self, keys: u16) {
+        self.emulator.set_keys(keys);
+    }
+
+    /// Loads a ROM and resets; raises ValueError if it is empty or too large.
+    fn load_rom(&mut self, rom: &[u8]) -> PyResult<()> {
+        self.emulator
+            .load_rom(rom)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+
+    /// Restarts the loaded ROM and releases all keys.
+    fn reset(&mut self) {
+        self.emulator.reset();
+    }
+
+    /// Runs `frames` 60 Hz frames of `tickrate` instructions each. Raises
+    /// RuntimeError once the ROM runs an instruction the machine cannot execute.
+    #[pyo3(signature = (frames=1))]
+    fn step_frame(&mut self, frames: u32) -> PyResult<()> {
+        for _ in 0..frames {
+            self.emulator.step_frame().map_err(fault_error)?;
+        }
+        Ok(())
+    }
+
+    /// Runs `cycles` instructions without touching the timers.
+    fn step_cycles(&mut self, cycles: u32) -> PyResult<()> {
+        self.emulator.step_cycles(cycles).map_err(fault_error)
+    }
+
+    fn press(&mut self, key: u8) -> PyResult<()> {
+        self.emulator.set_key(check_key(key)?, true);
+        Ok(())
+    }
+
+    fn release(&mut self, key: u8) -> PyResult<()> {
+        self.emulator.set_key(check_key(key)?, false);
+        Ok(())
+    }
+
+    /// The display as WIDTH * HEIGHT bytes of 0 or 1, row by row.
+    fn framebuffer<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.emulator.machine().display)
+    }
+
+    /// V0 to VF.
+    #[getter]
+    fn registers<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.emulator.machine().registers)
+    }
+
+    #[getter]
+    fn i(&self) -> u16 {
+        self.emulator.machine().i
+    }
+
+    #[getter]
+    fn pc(&self) -> usize {
+        self.emulator.machine().pc
+    }
+
+    #[getter]
+    fn delay_timer(&self) -> u8 {
+        self.emulator.machine().delay_timer
+    }
+
+    #[getter]
+    fn sound_timer(&self) -> u8 {
+        self.emulator.machine().sound_timer
+    }
+
+    /// Whether the beeper is sounding.
+    #[getter]
+    fn sound(&self) -> bool {
+        self.emulator.machine().sound_on()
+    }
+
+    /// `length` bytes of memory from `address`, up to the end of the 4 KiB by default.
+    /// Raises IndexError for a range past the end of memory.
+    #[pyo3(signature = (address=0, length=None))]
+    fn memory<'py>(
+        &self,
+        py: Python<'py>,
+        address: usize,
+        length: Option<usize>,
+    ) -> PyResult<Bound<'py, PyBytes>> {
+        let length = length.unwrap_or(machine::MEMORY_SIZE.saturating_sub(address));
+        let range = memory_range(address, length)?;
+        Ok(PyBytes::new(py, &self.emulator.machine().memory[range]))
+    }
+
+    /// Writes `data` to memory from `address`; raises IndexError like `memory`.
+    fn write_memory(&mut self, address: usize, data: &[u8]) -> PyResult<()> {
+        let range = memory_range(address, data.len())?;
+        self.emulator.machine_mut().memory[range].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// The whole machine state, random source included, to pass to `restore`.
+    fn snapshot<'py>(&self, py: Python<'py>) -> Bound<'py, PyBytes> {
+        PyBytes::new(py, &self.emulator.snapshot())
+    }
+
+    /// Goes back to a state from `snapshot`; raises ValueError if it is not one.
+    fn restore(&mut self, state: &[u8]) -> PyResult<()> {
+        self.emulator
+            .restore(state)
+            .map_err(|err| PyValueError::new_err(err.to_string()))
+    }
+}
+
+#[pymodule]
+#[pyo3(name = "chip8")]
+fn chip8_py(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<Chip8>()?;
+    module.add("WIDTH", machine::WIDTH)?;
+    module.add("HEIGHT", machine::HEIGHT)?;
+    Ok(())
+}
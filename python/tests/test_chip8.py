@@ -0,0 +1,60 @@
+"""Smoke test of the `chip8` module; run `maturin develop` in python/, then
+`python -m unittest discover python/tests`."""
+
+import unittest
+
+import chip8
+
+# V0 = 1, I = 0x20A, draw the 1 byte sprite there at (V0, V0), press-wait in V1, loop.
+ROM = bytes([0x60, 0x01, 0xA2, 0x0A, 0xD0, 0x01, 0xF1, 0x0A, 0x12, 0x08, 0xF0])
+
+
+class Chip8Test(unittest.TestCase):
+    def setUp(self):
+        self.emulator = chip8.Chip8(seed=1)
+        self.emulator.load_rom(ROM)
+
+    def test_steps_frames_and_draws(self):
+        self.emulator.step_frame(2)
+        pixels = self.emulator.framebuffer()
+        self.assertEqual(len(pixels), chip8.WIDTH * chip8.HEIGHT)
+        row = pixels[chip8.WIDTH : 2 * chip8.WIDTH]
+        self.assertEqual(list(row[:6]), [0, 1, 1, 1, 1, 0])
+        self.assertEqual(self.emulator.pc, 0x206)
+
+    def test_reads_and_writes_memory(self):
+        self.assertEqual(self.emulator.memory(0x200, len(ROM)), ROM)
+        self.assertEqual(len(self.emulator.memory()), 4096)
+        self.emulator.write_memory(0xFFE, b"\x12\x34")
+        self.assertEqual(self.emulator.memory(0xFFE), b"\x12\x34")
+        self.assertEqual(self.emulator.memory(0x1000), b"")
+
+    def test_memory_outside_of_memory_raises_index_error(self):
+        with self.assertRaises(IndexError):
+            self.emulator.memory(0xFFF, 2)
+        with self.assertRaises(IndexError):
+            self.emulator.memory(1, 2**64 - 1)
+        with self.assertRaises(IndexError):
+            self.emulator.memory(0x1001)
+        with self.assertRaises(IndexError):
+            self.emulator.write_memory(0xFFF, b"ab")
+
+    def test_snapshot_restores_the_machine(self):
+        self.emulator.step_frame()
+        snapshot = self.emulator.snapshot()
+        self.emulator.reset()
+        self.emulator.restore(snapshot)
+        self.assertEqual(self.emulator.pc, 0x206)
+        with self.assertRaises(ValueError):
+            self.emulator.restore(snapshot[1:])
+
+    def test_bad_roms_and_instructions_raise(self):
+        with self.assertRaises(ValueError):
+            self.emulator.load_rom(bytes(4000))
+        self.emulator.load_rom(bytes([0xE0, 0x00]))
+        with self.assertRaises(RuntimeError):
+            self.emulator.step_frame()
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,340 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::machine::{Machine, MEMORY_SIZE};
+
+/// Candidates listed in the cheat menu; narrow the search down to see the rest.
+const SHOWN_CANDIDATES: usize = 40;
+const PAGE: usize = 10;
+
+/// A byte of memory held at `value`.
+#[derive(Clone, Copy)]
+pub struct Cheat {
+    pub address: u16,
+    pub value: u8,
+}
+
+/// Cheats for one ROM, kept in a file of `ADDR=VALUE` hex lines with `#` comments.
+#[derive(Default)]
+pub struct CheatList {
+    pub cheats: Vec<Cheat>,
+}
+
+fn parse_hex(text: &str) -> Option<u16> {
+    let text = text.trim();
+    let digits = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u16::from_str_radix(digits, 16).ok()
+}
+
+impl CheatList {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut list = CheatList::default();
+        for (n, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            if line.is_empty() {
+                continue;
+            }
+            let cheat = line
+                .split_once('=')
+                .and_then(|(address, value)| {
+                    let address =
+                        parse_hex(address).filter(|&address| (address as usize) < MEMORY_SIZE)?;
+                    let value = u8::try_from(parse_hex(value)?).ok()?;
+                    Some(Cheat { address, value })
+                })
+                .ok_or_else(|| {
+                    format!("line {}: expected ADDR=VALUE in hex, got `{}`", n + 1, line)
+                })?;
+            list.set(cheat.address, cheat.value);
+        }
+        Ok(list)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+        Self::parse(&contents).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })
+    }
+
+    /// Writes the cheats for the ROM with SHA-1 `sha1`, creating the directory if needed.
+    pub fn save(&self, path: &Path, sha1: &str) -> io::Result<()> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)?;
+        }
+        let mut contents = format!("# Cheats for the ROM with SHA-1 {}\n", sha1);
+        for cheat in &self.cheats {
+            contents += &format!("{:03X}={:02X}\n", cheat.address, cheat.value);
+        }
+        fs::write(path, contents)
+    }
+
+    /// Holds `address` at `value`, replacing any cheat already on it.
+    pub fn set(&mut self, address: u16, value: u8) {
+        match self
+            .cheats
+            .iter_mut()
+            .find(|cheat| cheat.address == address)
+        {
+            Some(cheat) => cheat.value = value,
+            None => self.cheats.push(Cheat { address, value }),
+        }
+    }
+
+    pub fn remove(&mut self, address: u16) {
+        self.cheats.retain(|cheat| cheat.address != address);
+    }
+
+    /// Writes every cheat into memory; called once per frame.
+    pub fn apply(&self, machine: &mut Machine) {
+        for cheat in &self.cheats {
+            machine.memory[cheat.address as usize] = cheat.value;
+        }
+    }
+}
+
+/// `cheats/<sha1>.cht` in the config directory `config_dir`.
+pub fn default_path(config_dir: &Path, sha1: &str) -> PathBuf {
+    config_dir.join("cheats").join(format!("{}.cht", sha1))
+}
+
+/// How memory must have changed since the last snapshot for an address to stay.
+#[derive(Clone, Copy)]
+pub enum Filter {
+    Equal,
+    Changed,
+    Increased,
+    Decreased,
+    Value(u8),
+}
+
+impl Filter {
+    fn matches(self, old: u8, new: u8) -> bool {
+        match self {
+            Filter::Equal => new == old,
+            Filter::Changed => new != old,
+            Filter::Increased => new > old,
+            Filter::Decreased => new < old,
+            Filter::Value(value) => new == value,
+        }
+    }
+}
+
+/// Narrows memory down to the addresses that hold some value in the game, by
+/// comparing snapshots taken as it changes.
+pub struct RamSearch {
+    snapshot: Vec<u8>,
+    candidates: Vec<u16>,
+}
+
+impl RamSearch {
+    /// Starts over with every address as a candidate.
+    pub fn new(machine: &Machine) -> Self {
+        RamSearch {
+            snapshot: machine.memory.to_vec(),
+            candidates: (0..MEMORY_SIZE as u16).collect(),
+        }
+    }
+
+    /// Keeps the candidates matching `filter` and takes a new snapshot.
+    pub fn filter(&mut self, machine: &Machine, filter: Filter) {
+        let snapshot = &self.snapshot;
+        self.candidates.retain(|&address| {
+            let address = address as usize;
+            filter.matches(snapshot[address], machine.memory[address])
+        });
+        self.snapshot.copy_from_slice(&machine.memory);
+    }
+
+    pub fn candidates(&self) -> &[u16] {
+        &self.candidates
+    }
+
+    /// Value of `address` in the last snapshot.
+    pub fn previous(&self, address: u16) -> u8 {
+        self.snapshot[address as usize]
+    }
+}
+
+pub enum Action {
+    None,
+    Save,
+    Close,
+}
+
+/// The actions at the top of the cheat menu, before the candidates and cheats.
+static COMMANDS: [&str; 7] = [
+    "New search",
+    "Unchanged",
+    "Changed",
+    "Increased",
+    "Decreased",
+    "Equal to value",
+    "Save cheats",
+];
+
+/// Menu for searching memory and managing the cheat list. Hex digits set the value
+/// used by "Equal to value"; on a candidate, return freezes it at that value and
+/// space at its current value; on a cheat, return removes it.
+pub struct CheatMenu {
+    search: RamSearch,
+    value: u8,
+    selected: usize,
+}
+
+impl CheatMenu {
+    pub fn new(machine: &Machine) -> Self {
+        CheatMenu {
+            search: RamSearch::new(machine),
+            value: 0,
+            selected: 0,
+        }
+    }
+
+    fn shown_candidates(&self) -> &[u16] {
+        let candidates = self.search.candidates();
+        &candidates[..candidates.len().min(SHOWN_CANDIDATES)]
+    }
+
+    /// Handles a host key pressed while the menu is open.
+    pub fn input(&mut self, key: &str, cheats: &mut CheatList, machine: &Machine) -> Action {
+        let shown = self.shown_candidates().len();
+        let last = COMMANDS.len() + shown + cheats.cheats.len() - 1;
+        match key {
+            "up" => self.selected = self.selected.saturating_sub(1),
+            "down" => self.selected = (self.selected + 1).min(last),
+            "pageup" => self.selected = self.selected.saturating_sub(PAGE),
+            "pagedown" => self.selected = (self.selected + PAGE).min(last),
+            "home" => self.selected = 0,
+            "end" => self.selected = last,
+            "escape" => return Action::Close,
+            "backspace" => self.value >>= 4,
+            "return" | "space" => match self.selected {
+                0 => self.search = RamSearch::new(machine),
+                1 => self.search.filter(machine, Filter::Equal),
+                2 => self.search.filter(machine, Filter::Changed),
+                3 => self.search.filter(machine, Filter::Increased),
+                4 => self.search.filter(machine, Filter::Decreased),
+                5 => self.search.filter(machine, Filter::Value(self.value)),
+                6 => return Action::Save,
+                n if n < COMMANDS.len() + shown => {
+                    let address = self.shown_candidates()[n - COMMANDS.len()];
+                    let value = match key {
+                        "space" => machine.memory[address as usize],
+                        _ => self.value,
+                    };
+                    cheats.set(address, value);
+                }
+                n => {
+                    let address = cheats.cheats[n - COMMANDS.len() - shown].address;
+                    cheats.remove(address);
+                }
+            },
+            _ => {
+                if let Some(digit) = key.chars().next().filter(|_| key.len() == 1) {
+                    if let Some(digit) = digit.to_digit(16) {
+                        self.value = self.value << 4 | digit as u8;
+                    }
+                }
+            }
+        }
+        let last = COMMANDS.len() + self.shown_candidates().len() + cheats.cheats.len() - 1;
+        self.selected = self.selected.min(last);
+        Action::None
+    }
+
+    pub fn title(&self) -> String {
+        format!(
+            "Cheats: {} candidates, value {:02X}",
+            self.search.candidates().len(),
+            self.value
+        )
+    }
+
+    pub fn labels(&self, cheats: &CheatList, machine: &Machine) -> Vec<String> {
+        let mut labels: Vec<String> = COMMANDS.iter().map(|command| command.to_string()).collect();
+        labels[5] = format!("Equal to {:02X}", self.value);
+        for &address in self.shown_candidates() {
+            labels.push(format!(
+                "{:03X}: {:02X} (was {:02X})",
+                address,
+                machine.memory[address as usize],
+                self.search.previous(address)
+            ));
+        }
+        for cheat in &cheats.cheats {
+            labels.push(format!("Frozen {:03X}={:02X}", cheat.address, cheat.value));
+        }
+        labels
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+    use crate::machine::Quirks;
+
+    fn list(cheats: &CheatList) -> Vec<(u16, u8)> {
+        cheats
+            .cheats
+            .iter()
+            .map(|cheat| (cheat.address, cheat.value))
+            .collect()
+    }
+
+    #[test]
+    fn parse_reads_hex_lines_and_skips_comments() {
+        let text = "# Lives\n3F0=09\n0x3f1 = 0xFF  # ammo\n\n3F0=05\n";
+        let cheats = CheatList::parse(text).unwrap();
+        assert_eq!(list(&cheats), [(0x3F0, 0x05), (0x3F1, 0xFF)]);
+    }
+
+    #[test]
+    fn parse_rejects_bad_lines() {
+        let err = CheatList::parse("300=01\n300\n").err().unwrap();
+        assert_eq!(err, "line 2: expected ADDR=VALUE in hex, got `300`");
+        assert!(CheatList::parse("1000=01").is_err());
+        assert!(CheatList::parse("300=100").is_err());
+        assert!(CheatList::parse("300=zz").is_err());
+    }
+
+    #[test]
+    fn saved_cheats_load_back() {
+        let mut cheats = CheatList::default();
+        cheats.set(0x300, 0x42);
+        cheats.set(0xFFF, 0x01);
+        let path = env::temp_dir().join(format!("chip_8-cheats-{}.cht", process::id()));
+        cheats.save(&path, "ab").unwrap();
+        let loaded = CheatList::load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(list(&loaded.unwrap()), list(&cheats));
+    }
+
+    #[test]
+    fn ram_search_keeps_the_addresses_that_match() {
+        let mut machine = Machine::new(Quirks::default());
+        let mut search = RamSearch::new(&machine);
+        machine.memory[0x300] = 3;
+        machine.memory[0x301] = 3;
+        search.filter(&machine, Filter::Increased);
+        assert_eq!(search.candidates(), [0x300, 0x301]);
+        machine.memory[0x300] = 2;
+        search.filter(&machine, Filter::Value(2));
+        assert_eq!(search.candidates(), [0x300]);
+        assert_eq!(search.previous(0x300), 2);
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+use super::keymap::Layout;
+
+/// Settings that can be given in the config file, both as defaults and per ROM.
+#[derive(Clone, Default, Deserialize)]
+pub struct Settings {
+    pub scale: Option<u32>,
+    /// `RRGGBB,RRGGBB`, background then foreground.
+    pub palette: Option<String>,
+    pub layout: Option<Layout>,
+    /// CHIP-8 key (hex digit) to the host keys that press it.
+    pub keymap: Option<HashMap<String, Vec<String>>>,
+    pub quirks: Option<Vec<String>>,
+    pub tickrate: Option<u32>,
+    pub volume: Option<u8>,
+}
+
+impl Settings {
+    /// Fills every setting missing here from `defaults`.
+    fn or(self, defaults: Settings) -> Settings {
+        let keymap = match (defaults.keymap, self.keymap) {
+            (Some(mut keymap), Some(overrides)) => {
+                keymap.extend(overrides);
+                Some(keymap)
+            }
+            (keymap, overrides) => overrides.or(keymap),
+        };
+        Settings {
+            scale: self.scale.or(defaults.scale),
+            palette: self.palette.or(defaults.palette),
+            layout: self.layout.or(defaults.layout),
+            keymap,
+            quirks: self.quirks.or(defaults.quirks),
+            tickrate: self.tickrate.or(defaults.tickrate),
+            volume: self.volume.or(defaults.volume),
+        }
+    }
+}
+
+/// The TOML config file: top level settings apply to every ROM, and
+/// `[rom.<sha1>]` tables override them for the ROM with that SHA-1.
+#[derive(Default, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub defaults: Settings,
+    #[serde(default)]
+    pub rom: HashMap<String, Settings>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        toml::from_str(&contents).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })
+    }
+
+    /// Settings for the ROM with the given SHA-1: its `[rom.<sha1>]` table first, then
+    /// `recommended` (from the ROM database), then the top level defaults.
+    pub fn for_rom(&self, sha1: &str, recommended: Settings) -> Settings {
+        let rom = self
+            .rom
+            .iter()
+            .find(|(hash, _)| hash.eq_ignore_ascii_case(sha1))
+            .map(|(_, settings)| settings.clone())
+            .unwrap_or_default();
+        rom.or(recommended).or(self.defaults.clone())
+    }
+}
+
+/// `$XDG_CONFIG_HOME/chip_8`, falling back to `~/.config/chip_8`.
+pub fn dir() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))?;
+    Some(config_home.join("chip_8"))
+}
+
+pub fn rom_sha1(rom: &[u8]) -> String {
+    sha1_smol::Sha1::from(rom).digest().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn rom_tables_beat_the_database_which_beats_the_defaults() {
+        let config: Config = toml::from_str(&format!(
+            r#"
+            tickrate = 8
+            palette = "000000,ffffff"
+            scale = 6
+
+            [rom.{}]
+            tickrate = 20
+            "#,
+            SHA1.to_uppercase()
+        ))
+        .unwrap();
+        let recommended = Settings {
+            tickrate: Some(15),
+            palette: Some("112233,445566".to_string()),
+            ..Settings::default()
+        };
+
+        let settings = config.for_rom(SHA1, recommended.clone());
+        assert_eq!(settings.tickrate, Some(20));
+        assert_eq!(settings.palette.as_deref(), Some("112233,445566"));
+        assert_eq!(settings.scale, Some(6));
+
+        let other = config.for_rom(&SHA1.replace('0', "f"), recommended);
+        assert_eq!(other.tickrate, Some(15));
+        assert_eq!(config.for_rom(SHA1, Settings::default()).tickrate, Some(20));
+        let unknown = config.for_rom(&SHA1.replace('0', "f"), Settings::default());
+        assert_eq!(unknown.tickrate, Some(8));
+    }
+}
@@ -1,4 +0,0 @@
-pub static STACK_POINT_START: usize = 0x52;
-pub static PC_START: usize = 0x200;
-pub static FONT_FILE: &'static str = "FONT.chip8";
-pub static DELAY_INTERVAL: u32 = 17;
@@ -0,0 +1,402 @@
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+use super::config;
+use crate::emulator::Emulator;
+use crate::machine::{Machine, Quirks, Random, XorShift, MEMORY_SIZE};
+
+/// How a probed number is stored in memory.
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    /// One byte.
+    #[default]
+    Byte,
+    /// Big-endian 16-bit word.
+    Word,
+    /// One decimal digit per byte, most significant first, as FX33 writes them.
+    Bcd,
+}
+
+/// A number the game keeps at a fixed place in memory, like its score or lives.
+#[derive(Clone, Deserialize)]
+pub struct Probe {
+    #[serde(default)]
+    pub address: u16,
+    /// Reads VX instead of memory, for games that only keep the number in a register.
+    pub register: Option<u8>,
+    #[serde(default)]
+    pub encoding: Encoding,
+    /// Bytes read for `bcd`.
+    #[serde(default = "default_digits")]
+    pub digits: u16,
+}
+
+fn default_digits() -> u16 {
+    3
+}
+
+impl Probe {
+    pub fn read(&self, machine: &Machine) -> u32 {
+        if let Some(register) = self.register {
+            return machine.registers[register as usize % 16] as u32;
+        }
+        let byte = |offset: u16| {
+            machine.memory[self.address.wrapping_add(offset) as usize % MEMORY_SIZE] as u32
+        };
+        match self.encoding {
+            Encoding::Byte => byte(0),
+            Encoding::Word => byte(0) << 8 | byte(1),
+            Encoding::Bcd => (0..self.digits).fold(0, |value, digit| value * 10 + byte(digit)),
+        }
+    }
+}
+
+/// `VX` or the hex address the probe reads.
+impl fmt::Display for Probe {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.register {
+            Some(register) => write!(f, "V{:X}", register),
+            None => write!(f, "{:03X}", self.address),
+        }
+    }
+}
+
+/// Rewards the change of a probed number, times `scale`.
+#[derive(Clone, Deserialize)]
+pub struct RewardProbe {
+    #[serde(flatten)]
+    pub probe: Probe,
+    #[serde(default = "default_scale")]
+    pub scale: f64,
+}
+
+fn default_scale() -> f64 {
+    1.0
+}
+
+/// Ends the episode once a probed number meets every condition given.
+#[derive(Clone, Deserialize)]
+pub struct DoneProbe {
+    #[serde(flatten)]
+    pub probe: Probe,
+    pub equals: Option<u32>,
+    pub at_least: Option<u32>,
+    pub at_most: Option<u32>,
+}
+
+impl DoneProbe {
+    fn has_condition(&self) -> bool {
+        self.equals.is_some() || self.at_least.is_some() || self.at_most.is_some()
+    }
+
+    fn is_done(&self, machine: &Machine) -> bool {
+        let value = self.probe.read(machine);
+        self.equals.is_none_or(|equals| value == equals)
+            && self.at_least.is_none_or(|at_least| value >= at_least)
+            && self.at_most.is_none_or(|at_most| value <= at_most)
+    }
+}
+
+/// How to turn a game into an environment, loaded from TOML; see `envs/` for examples.
+#[derive(Clone, Deserialize)]
+pub struct EnvConfig {
+    pub name: String,
+    /// SHA-1 of the ROM the probes were written for; other ROMs are refused.
+    pub sha1: Option<String>,
+    /// Keys held for each action as hex digits, so `"14"` holds 1 and 4 and `""` nothing.
+    pub actions: Vec<String>,
+    /// Frames each action is held for.
+    #[serde(default = "default_frame_skip")]
+    pub frame_skip: u32,
+    /// Chance per frame of repeating the previous action instead of the chosen one.
+    #[serde(default)]
+    pub sticky_actions: f64,
+    #[serde(default = "default_tickrate")]
+    pub tickrate: u32,
+    pub quirks: Option<Vec<String>>,
+    /// Frames after which an episode ends anyway.
+    pub max_frames: Option<u64>,
+    #[serde(default)]
+    pub reward: Vec<RewardProbe>,
+    #[serde(default)]
+    pub done: Vec<DoneProbe>,
+}
+
+fn default_frame_skip() -> u32 {
+    4
+}
+
+fn default_tickrate() -> u32 {
+    10
+}
+
+impl EnvConfig {
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let config: EnvConfig = toml::from_str(text).map_err(|err| err.to_string())?;
+        let probes = config.reward.iter().map(|reward| &reward.probe);
+        let probes = probes.chain(config.done.iter().map(|done| &done.probe));
+        if let Some(register) = probes.filter_map(|probe| probe.register).find(|&r| r > 0xF) {
+            return Err(format!("there is no register V{:X}", register));
+        }
+        // A done probe without conditions would end every episode on its first frame.
+        if let Some(done) = config.done.iter().find(|done| !done.has_condition()) {
+            return Err(format!(
+                "done probe on {} needs `equals`, `at_least` or `at_most`",
+                done.probe
+            ));
+        }
+        Ok(config)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let contents = fs::read_to_string(path)?;
+        Self::parse(&contents).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), err),
+            )
+        })
+    }
+}
+
+/// Parses `"14"` into a key mask with keys 1 and 4 held.
+fn parse_action(keys: &str) -> Result<u16, String> {
+    keys.chars()
+        .try_fold(0, |mask, key| match key.to_digit(16) {
+            Some(key) => Ok(mask | 1 << key),
+            None => Err(format!("`{}` in action `{}` is not a hex key", key, keys)),
+        })
+}
+
+/// `Env::step` was given an action at or past `action_count`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct NoSuchAction(pub usize);
+
+impl fmt::Display for NoSuchAction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "there is no action {}", self.0)
+    }
+}
+
+impl Error for NoSuchAction {}
+
+/// A game run headless as a reinforcement learning environment: observations are
+/// the display, one byte per pixel, and rewards come from the config's probes.
+pub struct Env {
+    emulator: Emulator,
+    config: EnvConfig,
+    actions: Vec<u16>,
+    /// Decides sticky actions, apart from the emulator's random source so they don't
+    /// change the game.
+    sticky: XorShift,
+    last_action: usize,
+    /// Reward probe values after the last frame.
+    scores: Vec<u32>,
+    frames: u64,
+}
+
+impl Env {
+    pub fn new(rom: Vec<u8>, config: EnvConfig, seed: u64) -> Result<Self, Box<dyn Error>> {
+        if let Some(sha1) = &config.sha1 {
+            let rom_sha1 = config::rom_sha1(&rom);
+            if !sha1.eq_ignore_ascii_case(&rom_sha1) {
+                return Err(format!(
+                    "{} is set up for the ROM with SHA-1 {}, not {}",
+                    config.name, sha1, rom_sha1
+                )
+                .into());
+            }
+        }
+        let actions = config
+            .actions
+            .iter()
+            .map(|keys| parse_action(keys))
+            .collect::<Result<Vec<_>, _>>()?;
+        if actions.is_empty() {
+            return Err(format!("{} has no actions", config.name).into());
+        }
+        let quirks: Quirks = config
+            .quirks
+            .clone()
+            .unwrap_or_default()
+            .join(",")
+            .parse()?;
+        let mut emulator = Emulator::new(quirks, seed);
+        emulator.tickrate = config.tickrate;
+        emulator.load_rom(&rom)?;
+        Ok(Env {
+            emulator,
+            actions,
+            sticky: XorShift::new(seed.rotate_left(32) ^ 0x5EED),
+            last_action: 0,
+            scores: vec![0; config.reward.len()],
+            frames: 0,
+            config,
+        })
+    }
+
+    /// Number of actions; `step` takes an index below it.
+    pub fn action_count(&self) -> usize {
+        self.actions.len()
+    }
+
+    /// Starts a new episode and returns the first observation.
+    pub fn reset(&mut self) -> Vec<u8> {
+        self.emulator.reset();
+        self.last_action = 0;
+        self.frames = 0;
+        self.scores = self.read_scores();
+        self.machine().display.to_vec()
+    }
+
+    /// Holds the keys of `action` for `frame_skip` frames, or until the episode ends.
+    /// Returns the observation, the reward gathered and whether the episode is over.
+    pub fn step(&mut self, action: usize) -> Result<(Vec<u8>, f64, bool), NoSuchAction> {
+        if action >= self.actions.len() {
+            return Err(NoSuchAction(action));
+        }
+        let mut reward = 0.0;
+        let mut done = false;
+        for _ in 0..self.config.frame_skip.max(1) {
+            let sticky = f64::from(self.sticky.random_byte()) < self.config.sticky_actions * 256.0;
+            if !sticky {
+                self.last_action = action;
+            }
+            self.emulator.set_keys(self.actions[self.last_action]);
+            let halted = self.emulator.step_frame().is_err();
+            self.frames += 1;
+
+            let scores = self.read_scores();
+            for ((probe, old), new) in self.config.reward.iter().zip(&self.scores).zip(&scores) {
+                reward += probe.scale * (f64::from(*new) - f64::from(*old));
+            }
+            self.scores = scores;
+            done = self
+                .config
+                .done
+                .iter()
+                .any(|probe| probe.is_done(self.emulator.machine()))
+                || self.config.max_frames.is_some_and(|max| self.frames >= max)
+                || halted;
+            if done {
+                break;
+            }
+        }
+        Ok((self.machine().display.to_vec(), reward, done))
+    }
+
+    pub fn machine(&self) -> &Machine {
+        self.emulator.machine()
+    }
+
+    fn read_scores(&self) -> Vec<u32> {
+        self.config
+            .reward
+            .iter()
+            .map(|reward| reward.probe.read(self.machine()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// While key 1 is held V5 counts up; FX33 keeps its BCD at 0x300.
+    const COUNTER: [u8; 12] = [
+        0xA3, 0x00, 0x61, 0x01, 0xE1, 0xA1, 0x75, 0x01, 0xF5, 0x33, 0x12, 0x04,
+    ];
+
+    const CONFIG: &str = r#"
+        name = "Counter"
+        actions = ["", "1"]
+        frame_skip = 1
+
+        [[reward]]
+        register = 5
+        scale = -0.5
+
+        [[done]]
+        address = 0x300
+        encoding = "bcd"
+        at_least = 20
+    "#;
+
+    fn probe(toml: &str) -> Probe {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn probes_read_each_encoding() {
+        let mut machine = Machine::new(Quirks::default());
+        machine.reset(&[0x12, 0x00]).unwrap();
+        machine.memory[0x300..0x303].copy_from_slice(&[1, 2, 3]);
+        machine.registers[0xA] = 42;
+        assert_eq!(probe("address = 0x300").read(&machine), 1);
+        assert_eq!(
+            probe("address = 0x300\nencoding = 'word'").read(&machine),
+            0x102
+        );
+        assert_eq!(
+            probe("address = 0x300\nencoding = 'bcd'").read(&machine),
+            123
+        );
+        let two_digits = "address = 0x301\nencoding = 'bcd'\ndigits = 2";
+        assert_eq!(probe(two_digits).read(&machine), 23);
+        assert_eq!(probe("register = 0xA").read(&machine), 42);
+    }
+
+    #[test]
+    fn step_rewards_probe_changes_scaled_and_stops_when_done() {
+        let config = EnvConfig::parse(CONFIG).unwrap();
+        let mut env = Env::new(COUNTER.to_vec(), config, 1).unwrap();
+        assert_eq!(env.action_count(), 2);
+        assert_eq!(
+            env.reset().len(),
+            crate::machine::WIDTH * crate::machine::HEIGHT
+        );
+
+        let (_, reward, done) = env.step(0).unwrap();
+        assert_eq!((reward, done), (0.0, false));
+        let (_, reward, done) = env.step(1).unwrap();
+        let counted = env.machine().registers[5];
+        assert!(counted > 0);
+        assert_eq!(reward, -0.5 * f64::from(counted));
+        assert!(!done);
+
+        let mut steps = 0;
+        while !env.step(1).unwrap().2 {
+            steps += 1;
+            assert!(steps < 100, "done probe never fired");
+        }
+        assert!(env.machine().registers[5] >= 20);
+
+        env.reset();
+        assert_eq!(env.machine().registers[5], 0);
+        assert_eq!(env.step(0).unwrap().1, 0.0);
+        assert_eq!(env.step(2).err(), Some(NoSuchAction(2)));
+    }
+
+    #[test]
+    fn done_probe_without_conditions_is_rejected() {
+        let config = "name = 'x'\nactions = ['']\n[[done]]\nregister = 3";
+        let err = EnvConfig::parse(config).err().unwrap();
+        assert!(err.contains("V3"), "{}", err);
+        let config = "name = 'x'\nactions = ['']\n[[reward]]\nregister = 16";
+        assert!(EnvConfig::parse(config).is_err());
+    }
+
+    #[test]
+    fn bundled_configs_load() {
+        for entry in fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/envs")).unwrap() {
+            let path = entry.unwrap().path();
+            EnvConfig::load(&path).unwrap();
+        }
+    }
+}
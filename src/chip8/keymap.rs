@@ -0,0 +1,238 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// CHIP-8 keys in the order they sit on the COSMAC VIP keypad, row by row.
+pub static KEYPAD_ORDER: [u8; 16] = [
+    0x1, 0x2, 0x3, 0xC, 0x4, 0x5, 0x6, 0xD, 0x7, 0x8, 0x9, 0xE, 0xA, 0x0, 0xB, 0xF,
+];
+
+#[rustfmt::skip]
+static QWERTY: [&[&str]; 16] = [
+    &["1"], &["2"], &["3"], &["4"],
+    &["q"], &["w"], &["e"], &["r"],
+    &["a"], &["s"], &["d"], &["f"],
+    &["z"], &["x"], &["c"], &["v"],
+];
+#[rustfmt::skip]
+// AZERTY number rows report either the digit or the unshifted symbol.
+static AZERTY: [&[&str]; 16] = [
+    &["1", "&"], &["2", "é"], &["3", "\""], &["4", "'"],
+    &["a"], &["z"], &["e"], &["r"],
+    &["q"], &["s"], &["d"], &["f"],
+    &["w"], &["x"], &["c"], &["v"],
+];
+#[rustfmt::skip]
+static DVORAK: [&[&str]; 16] = [
+    &["1"], &["2"], &["3"], &["4"],
+    &["'"], &[","], &["."], &["p"],
+    &["a"], &["o"], &["e"], &["u"],
+    &[";"], &["q"], &["j"], &["k"],
+];
+
+/// Controller bindings every layout starts with: D-pad and left stick on the
+/// 5/7/8/9 cross most games steer with, face buttons on the keys around it.
+static PAD_DEFAULTS: [(&str, u8); 12] = [
+    ("pad dpup", 0x5),
+    ("pad dpleft", 0x7),
+    ("pad dpdown", 0x8),
+    ("pad dpright", 0x9),
+    ("pad lefty-", 0x5),
+    ("pad leftx-", 0x7),
+    ("pad lefty+", 0x8),
+    ("pad leftx+", 0x9),
+    ("pad a", 0x6),
+    ("pad b", 0x4),
+    ("pad x", 0x1),
+    ("pad y", 0x2),
+];
+
+#[derive(Clone, Copy, Default, Deserialize, ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    #[default]
+    Qwerty,
+    Azerty,
+    Dvorak,
+}
+
+/// A `--bind` flag: one CHIP-8 key and the host keys that press it.
+#[derive(Clone)]
+pub struct Binding {
+    pub key: u8,
+    pub host_keys: Vec<String>,
+}
+
+/// Maps host key names (lowercase SDL key names, e.g. `q`, `up`, `keypad 8`, or
+/// controller inputs such as `pad a`, `pad dpup`, `pad leftx-`) to CHIP-8 keys.
+#[derive(Clone, Default)]
+pub struct Keymap {
+    keys: HashMap<String, u8>,
+}
+
+impl Keymap {
+    pub fn from_layout(layout: Layout) -> Self {
+        let table = match layout {
+            Layout::Qwerty => &QWERTY,
+            Layout::Azerty => &AZERTY,
+            Layout::Dvorak => &DVORAK,
+        };
+        let mut keymap = Keymap::default();
+        for (&key, host_keys) in KEYPAD_ORDER.iter().zip(table) {
+            for host_key in host_keys.iter() {
+                keymap.bind(key, host_key);
+            }
+        }
+        for (host_key, key) in PAD_DEFAULTS {
+            keymap.bind(key, host_key);
+        }
+        keymap
+    }
+
+    pub fn get(&self, host_key: &str) -> Option<u8> {
+        self.keys.get(host_key).copied()
+    }
+
+    pub fn bind(&mut self, key: u8, host_key: &str) {
+        self.keys.insert(host_key.to_lowercase(), key);
+    }
+
+    pub fn unbind(&mut self, key: u8) {
+        self.keys.retain(|_, bound| *bound != key);
+    }
+
+    /// Replaces the host keys of `binding.key` with the ones in `binding`.
+    pub fn apply(&mut self, binding: &Binding) {
+        self.unbind(binding.key);
+        for host_key in &binding.host_keys {
+            self.bind(binding.key, host_key);
+        }
+    }
+
+    /// Applies a keymap file, see `apply_config`.
+    pub fn load(&mut self, path: &Path, rom_sha1: &str) -> io::Result<()> {
+        let contents = fs::read_to_string(path)?;
+        self.apply_config(&contents, rom_sha1).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}", path.display(), err),
+            )
+        })
+    }
+
+    /// Applies `HEX = host key, host key` lines, optionally followed by `[rom.<sha1>]`
+    /// sections that only apply to the ROM with that SHA-1, as in the config file.
+    /// Errors start with the line number.
+    pub fn apply_config(&mut self, contents: &str, rom_sha1: &str) -> Result<(), String> {
+        let mut in_scope = true;
+        for (number, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                let sha1 = section.trim().strip_prefix("rom.").ok_or_else(|| {
+                    format!("{}: expected [rom.<sha1>], got `{}`", number + 1, line)
+                })?;
+                in_scope = sha1.eq_ignore_ascii_case(rom_sha1);
+                continue;
+            }
+            if in_scope {
+                let binding =
+                    parse_binding(line).map_err(|err| format!("{}: {}", number + 1, err))?;
+                self.apply(&binding);
+            }
+        }
+        Ok(())
+    }
+
+    /// Formats the keymap in the keymap file syntax.
+    pub fn to_config(&self) -> String {
+        let mut config = String::new();
+        for key in KEYPAD_ORDER {
+            let mut host_keys: Vec<&str> = self
+                .keys
+                .iter()
+                .filter(|(_, &bound)| bound == key)
+                .map(|(host_key, _)| host_key.as_str())
+                .collect();
+            host_keys.sort();
+            config.push_str(&format!("{:X} = {}\n", key, host_keys.join(", ")));
+        }
+        config
+    }
+}
+
+/// Parses `HEX=host key[,host key...]`, e.g. `5=w,up`.
+pub fn parse_binding(value: &str) -> Result<Binding, String> {
+    let (key, host_keys) = value
+        .split_once('=')
+        .ok_or_else(|| format!("expected HEX=KEY, got `{}`", value))?;
+    let key = key.trim();
+    let key = u8::from_str_radix(key.trim_start_matches("0x"), 16)
+        .ok()
+        .filter(|&key| key <= 0xF)
+        .ok_or_else(|| format!("`{}` is not a CHIP-8 key (0-F)", key))?;
+    let host_keys: Vec<String> = host_keys
+        .split(',')
+        .map(|host_key| host_key.trim().to_lowercase())
+        .filter(|host_key| !host_key.is_empty())
+        .collect();
+    if host_keys.is_empty() {
+        return Err(format!("no host keys given for key {:X}", key));
+    }
+    Ok(Binding { key, host_keys })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHA1: &str = "0123456789abcdef0123456789abcdef01234567";
+
+    #[test]
+    fn parse_binding_reads_a_key_and_its_host_keys() {
+        let binding = parse_binding("0xA = W, Up").unwrap();
+        assert_eq!(
+            (binding.key, binding.host_keys),
+            (0xA, vec!["w".into(), "up".into()])
+        );
+        assert!(parse_binding("10=w").is_err());
+        assert!(parse_binding("5=").is_err());
+        assert!(parse_binding("5").is_err());
+    }
+
+    #[test]
+    fn rom_sections_apply_to_the_rom_with_that_sha1() {
+        let config = format!(
+            "# Arrows for everything\n5 = up\n\n[rom.{}]\n5 = k\n[rom.{}]\n5 = j\n",
+            SHA1.to_uppercase(),
+            "f".repeat(40)
+        );
+        let mut keymap = Keymap::default();
+        keymap.apply_config(&config, SHA1).unwrap();
+        assert_eq!(
+            (keymap.get("k"), keymap.get("up"), keymap.get("j")),
+            (Some(5), None, None)
+        );
+
+        let mut keymap = Keymap::default();
+        keymap.apply_config(&config, &"0".repeat(40)).unwrap();
+        assert_eq!(keymap.get("up"), Some(5));
+    }
+
+    #[test]
+    fn bad_lines_report_their_number() {
+        let mut keymap = Keymap::default();
+        let err = keymap
+            .apply_config("1 = x\n[pong.ch8]\n", SHA1)
+            .unwrap_err();
+        assert_eq!(err, "2: expected [rom.<sha1>], got `[pong.ch8]`");
+        let err = keymap.apply_config("1 = x\nG = y\n", SHA1).unwrap_err();
+        assert!(err.starts_with("2: "), "{}", err);
+    }
+}
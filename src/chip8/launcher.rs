@@ -0,0 +1,167 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::config;
+use super::rom::{self, ROM_EXTENSIONS};
+use super::romdb::RomDatabase;
+
+const PAGE: usize = 10;
+
+struct Entry {
+    path: PathBuf,
+    label: String,
+    is_dir: bool,
+}
+
+pub enum Action {
+    None,
+    Launch(PathBuf),
+    Close,
+}
+
+/// Menu listing the ROMs and subdirectories of a directory, with the ROMs inside zip
+/// archives listed as `pack.zip:rom.ch8`.
+pub struct Launcher {
+    dir: PathBuf,
+    entries: Vec<Entry>,
+    selected: usize,
+    database: RomDatabase,
+    /// Archives and directories that could not be read, for the caller to report.
+    warnings: Vec<String>,
+}
+
+impl Launcher {
+    pub fn open(dir: &Path, database: RomDatabase) -> io::Result<Self> {
+        let mut launcher = Self {
+            dir: PathBuf::new(),
+            entries: Vec::new(),
+            selected: 0,
+            database,
+            warnings: Vec::new(),
+        };
+        launcher.change_dir(dir)?;
+        Ok(launcher)
+    }
+
+    fn change_dir(&mut self, dir: &Path) -> io::Result<()> {
+        let dir = dir.canonicalize()?;
+        let mut dirs = Vec::new();
+        let mut roms = Vec::new();
+        for entry in fs::read_dir(&dir)? {
+            let path = entry?.path();
+            let name = path
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            if name.starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                dirs.push(Entry {
+                    path,
+                    label: format!("{}/", name),
+                    is_dir: true,
+                });
+            } else if rom::is_archive(&name) {
+                match rom::read_archive(&path.to_string_lossy()) {
+                    Ok(inner) => roms.extend(inner.into_iter().map(|(inner, rom)| {
+                        let path = PathBuf::from(format!("{}:{}", path.display(), inner));
+                        self.rom_entry(path, format!("{}:{}", name, inner), rom.ok())
+                    })),
+                    Err(err) => self.warnings.push(err.to_string()),
+                }
+            } else if has_rom_extension(&path) {
+                let rom = rom::load(&path.to_string_lossy()).ok().map(|(_, rom)| rom);
+                roms.push(self.rom_entry(path, name, rom));
+            }
+        }
+        dirs.sort_by(|a, b| a.label.cmp(&b.label));
+        roms.sort_by_key(|entry| entry.label.to_lowercase());
+
+        self.entries.clear();
+        if let Some(parent) = dir.parent() {
+            self.entries.push(Entry {
+                path: parent.to_path_buf(),
+                label: "../".to_string(),
+                is_dir: true,
+            });
+        }
+        self.entries.extend(dirs);
+        self.entries.extend(roms);
+        self.dir = dir;
+        self.selected = 0;
+        Ok(())
+    }
+
+    /// A ROM labelled with its title in the ROM database, if it is there.
+    fn rom_entry(&self, path: PathBuf, name: String, rom: Option<Vec<u8>>) -> Entry {
+        let label = match rom.and_then(|rom| self.database.lookup(&config::rom_sha1(&rom))) {
+            Some(info) => format!("{} ({})", info.title, name),
+            None => name,
+        };
+        Entry {
+            path,
+            label,
+            is_dir: false,
+        }
+    }
+
+    /// Handles a host key pressed while the launcher is open.
+    pub fn input(&mut self, key: &str) -> Action {
+        let last = self.entries.len().saturating_sub(1);
+        match key {
+            "up" => self.selected = self.selected.saturating_sub(1),
+            "down" => self.selected = (self.selected + 1).min(last),
+            "pageup" => self.selected = self.selected.saturating_sub(PAGE),
+            "pagedown" => self.selected = (self.selected + PAGE).min(last),
+            "home" => self.selected = 0,
+            "end" => self.selected = last,
+            "escape" => return Action::Close,
+            "return" => {
+                let Some(entry) = self.entries.get(self.selected) else {
+                    return Action::None;
+                };
+                if !entry.is_dir {
+                    return Action::Launch(entry.path.clone());
+                }
+                let dir = entry.path.clone();
+                if let Err(err) = self.change_dir(&dir) {
+                    self.warnings
+                        .push(format!("Unable to open {}: {}", dir.display(), err));
+                }
+            }
+            _ => {}
+        }
+        Action::None
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn title(&self) -> String {
+        self.dir.display().to_string()
+    }
+
+    pub fn labels(&self) -> Vec<String> {
+        self.entries
+            .iter()
+            .map(|entry| entry.label.clone())
+            .collect()
+    }
+
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn take_warnings(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.warnings)
+    }
+}
+
+fn has_rom_extension(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+}
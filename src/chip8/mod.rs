@@ -1,270 +1,1020 @@
 use std::collections::HashSet;
+use std::error::Error;
 use std::fs::File;
-use std::io::Write;
-use std::{ops::Shr, panic};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
-mod cpu_const;
-pub mod disasm;
+use crate::disasm;
+use crate::machine::cpu_const::PC_START;
+use crate::machine::{Machine, XorShift};
+
+pub mod cheats;
+pub mod config;
+pub mod env;
+pub mod keymap;
+pub mod launcher;
+pub mod movie;
 pub mod options;
+pub mod profiler;
+pub mod recorder;
+pub mod rom;
+pub mod romdb;
 pub mod screen;
+pub mod screenshot;
+#[cfg(feature = "scripting")]
+pub mod script;
+pub mod sprites;
 pub mod timers;
 
 pub struct Chip8 {
     pub running: bool,
-    wainting: bool,
+    machine: Machine,
     timers: timers::Timers,
     screen: screen::Screen,
-    keys: HashSet<u8>,
-    memory: [u8; 4096],
-    registers: [u8; 16],
-    i: u16,
-    pc: usize,
-    last_pc: usize,
-    sp: usize,
-    cycles: usize,
-    whole: u16,
+    rng: XorShift,
+    tickrate: u32,
+    frontend: options::Frontend,
+    frames: usize,
+    frame_limit: Option<usize>,
+    screenshot_at: Option<usize>,
+    screenshot_file: String,
+    record_file: Option<String>,
+    recorder: Option<recorder::Recorder>,
+    /// Recordings started so far, to number the ones after the first `--record` one.
+    recordings: usize,
+    movie_record_file: Option<String>,
+    movie_recording: Option<movie::Movie>,
+    /// Movie being played back and the index of its next frame.
+    playback: Option<(movie::Movie, usize)>,
+    /// Cheat and script writes of the frame being played back.
+    replay_writes: Vec<movie::Event>,
+    /// Instructions run so far in this frame, to time the writes of a movie.
+    frame_cycle: u32,
+    paused: bool,
+    frame_advance: bool,
+    /// Index into `SPEEDS`.
+    speed: usize,
+    rom: Vec<u8>,
+    rom_file: String,
+    rom_db: Option<String>,
+    launcher: Option<launcher::Launcher>,
+    /// Directory the launcher was last opened in.
+    launcher_dir: Option<PathBuf>,
+    profile_dir: Option<PathBuf>,
+    profiler: Option<profiler::Profiler>,
+    cheats: cheats::CheatList,
+    /// Where the cheat menu saves `cheats`; the ROM's file in the config directory if `None`.
+    cheat_file: Option<PathBuf>,
+    /// Kept while closed so a search can go on over several visits.
+    cheat_menu: Option<cheats::CheatMenu>,
+    cheat_menu_open: bool,
+    /// Whether the debug window is open.
+    debug: bool,
+    sprites_file: Option<String>,
+    sprite_region: Option<Range<usize>>,
+    sprite_layout: sprites::SpriteLayout,
+    #[cfg(feature = "scripting")]
+    script: Option<script::Script>,
+    /// The command line, to resolve the settings of each ROM the launcher starts.
+    options: options::Chip8Options,
 }
 
+/// Emulation speeds selectable with the slower/faster hotkeys; `None` is uncapped.
+static SPEEDS: [Option<f64>; 6] = [Some(0.25), Some(0.5), Some(1.0), Some(2.0), Some(4.0), None];
+static NORMAL_SPEED: usize = 2;
+/// Size of a sprite pixel in `--sprites` exports.
+const SPRITE_SCALE: u32 = 4;
+
 impl Chip8 {
-    pub fn new_with_rom(options: options::Chip8Options) -> Self {
+    /// Fails when the frontend cannot start, like the tty one outside a terminal.
+    pub fn new_with_rom(options: options::Chip8Options) -> io::Result<Self> {
+        let screen = screen::Screen::new(
+            options.scale_factor,
+            options.frontend,
+            options.keymap.clone(),
+            options.palette,
+            options.volume,
+        )?;
+        Ok(Self::with_screen(options, screen))
+    }
+
+    /// Like `new_with_rom`, but shows the game on a backend supplied by the caller
+    /// instead of the one picked by `options.frontend`.
+    pub fn with_backend(options: options::Chip8Options, backend: Box<dyn screen::Backend>) -> Self {
+        let screen = screen::Screen::with_backend(
+            backend,
+            options.scale_factor,
+            options.keymap.clone(),
+            options.palette,
+        );
+        Self::with_screen(options, screen)
+    }
+
+    fn with_screen(mut options: options::Chip8Options, screen: screen::Screen) -> Self {
+        let seed = options.seed.unwrap_or_else(rand::random);
         let mut chip = Chip8 {
             running: true,
-            wainting: false,
+            machine: Machine::new(options.quirks),
             timers: timers::Timers::new(),
-            screen: screen::Screen::new(options.scale_factor),
-            keys: HashSet::new(),
-            memory: [0; 4096],
-            registers: [0; 16],
-            i: 0,
-            pc: cpu_const::PC_START,
-            last_pc: 0,
-            sp: cpu_const::STACK_POINT_START,
-            cycles: 0,
-            whole: 0,
+            screen,
+            rng: XorShift::new(seed),
+            tickrate: options.tickrate,
+            frontend: options.frontend,
+            frames: 0,
+            frame_limit: options.frame_limit,
+            screenshot_at: options.screenshot_at,
+            screenshot_file: options.screenshot_file.clone(),
+            record_file: options.record_file.clone(),
+            recorder: None,
+            recordings: 0,
+            movie_record_file: options.movie_record_file.clone(),
+            movie_recording: None,
+            playback: options.movie.take().map(|movie| (movie, 0)),
+            replay_writes: Vec::new(),
+            frame_cycle: 0,
+            paused: false,
+            frame_advance: false,
+            speed: NORMAL_SPEED,
+            rom_db: options.rom_db.clone(),
+            launcher: None,
+            launcher_dir: options.launcher_dir.clone().map(PathBuf::from),
+            profile_dir: options.profile_dir.clone().map(PathBuf::from),
+            profiler: options.profile_dir.is_some().then(profiler::Profiler::new),
+            cheats: std::mem::take(&mut options.cheats),
+            cheat_file: options.cheat_file.clone().map(PathBuf::from),
+            cheat_menu: None,
+            cheat_menu_open: false,
+            debug: false,
+            sprites_file: options.sprites_file.clone(),
+            sprite_region: options.sprite_region.clone(),
+            sprite_layout: options.sprite_layout,
+            #[cfg(feature = "scripting")]
+            script: options.script.take(),
+            rom_file: options.file.clone(),
+            rom: std::mem::take(&mut options.rom),
+            options,
         };
-        let font = include_bytes!("../../FONTS.chip8");
-        chip.load_rom(font, 0);
-        chip.load_rom(&options.rom, 0x200);
+        chip.reset();
         chip.screen.start_screen();
+        if let Some(title) = chip.options.title.clone() {
+            chip.screen.set_title(&title);
+        }
+        if chip.record_file.is_some() {
+            chip.start_recording();
+        }
+        if chip.movie_record_file.is_some() {
+            let sha1 = config::rom_sha1(&chip.rom);
+            chip.movie_recording = Some(movie::Movie::new(
+                seed,
+                chip.tickrate,
+                chip.machine.quirks,
+                sha1,
+            ));
+        }
+        // A movie that starts in the launcher replays the ROM it picked instead.
+        if chip.launcher_dir.is_some() && chip.playback.is_none() {
+            chip.open_launcher();
+        }
+        #[cfg(feature = "scripting")]
+        if chip.script.is_some() {
+            chip.record_writes(|chip| {
+                if let Some(script) = &mut chip.script {
+                    script.start(&mut chip.machine);
+                }
+            });
+        }
         chip
     }
 
+    /// Runs one 60 Hz frame: reads the keypad, executes `tickrate` instructions and
+    /// counts the timers down, then waits for the next frame unless headless.
+    /// While paused only hotkeys are handled, until a frame advance is requested.
+    pub fn frame(&mut self) {
+        let (keys, running) = self.screen.get_key_state();
+        self.running &= running;
+        self.handle_hotkeys();
+        if self.launcher.is_some() || self.cheat_menu_open {
+            if self.frontend != options::Frontend::Headless {
+                self.timers.wait_for_next_frame(1.0);
+            }
+            return;
+        }
+        if !self.paused || std::mem::take(&mut self.frame_advance) {
+            self.frame_cycle = 0;
+            self.replay_events();
+            self.update_keys(keys);
+            self.apply_cheats();
+            for _ in 0..self.tickrate {
+                self.cycle();
+            }
+            self.replay_writes();
+            self.machine.tick_timers();
+            self.screen.set_sound(self.machine.sound_on());
+            self.frames += 1;
+            self.end_frame();
+            self.run_frame_script();
+            if let Some(movie) = &mut self.movie_recording {
+                movie.frames.push(self.machine.keys());
+            }
+            if self.frame_limit == Some(self.frames) {
+                self.running = false;
+            }
+        }
+        if self.frontend != options::Frontend::Headless {
+            match SPEEDS[self.speed] {
+                _ if self.paused => self.timers.wait_for_next_frame(1.0),
+                Some(speed) => self.timers.wait_for_next_frame(speed),
+                None => {}
+            }
+        }
+    }
+
+    fn update_keys(&mut self, keys: HashSet<u8>) {
+        let mut mask = movie::keys_to_mask(&keys);
+        if let Some((movie, next)) = &mut self.playback {
+            if let Some(&frame) = movie.frames.get(*next) {
+                mask = frame;
+                *next += 1;
+            } else {
+                let message = format!("Movie finished after {} frames", next);
+                self.playback = None;
+                self.screen.notify(&message);
+                if self.frontend == options::Frontend::Headless {
+                    self.running = false;
+                }
+            }
+        }
+        // Script presses go into the movie too, so it replays without the script.
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &self.script {
+            mask |= script.keys();
+        }
+        self.machine.set_keys(mask);
+    }
+
+    /// Repeats the resets and ROM changes of the movie frame about to be played, and
+    /// queues its cheat and script writes for `replay_writes`.
+    fn replay_events(&mut self) {
+        self.replay_writes.clear();
+        let Some((movie, next)) = &self.playback else {
+            return;
+        };
+        let events: Vec<movie::Event> = movie.events_at(*next).cloned().collect();
+        for event in events {
+            match event {
+                movie::Event::Reset => self.reset(),
+                movie::Event::Rom { sha1, path } => {
+                    if let Err(err) = self.replay_rom(&sha1, &path) {
+                        self.screen.warn(&format!("Stopping the movie: {}", err));
+                        self.screen.set_status(&err);
+                        self.playback = None;
+                        return;
+                    }
+                }
+                write => self.replay_writes.push(write),
+            }
+        }
+    }
+
+    fn replay_rom(&mut self, sha1: &str, path: &str) -> Result<(), String> {
+        let (name, rom) = rom::load(path).map_err(|err| err.to_string())?;
+        if config::rom_sha1(&rom) != sha1 {
+            return Err(format!(
+                "{} is not the ROM the movie was recorded with",
+                name
+            ));
+        }
+        self.load(name, rom).map_err(|err| err.to_string())
+    }
+
+    /// Applies the queued movie writes timed before the next instruction.
+    fn replay_writes(&mut self) {
+        let cycle = self.frame_cycle;
+        for write in &self.replay_writes {
+            if write.cycle() == Some(cycle) {
+                write.apply(&mut self.machine);
+            }
+        }
+    }
+
+    /// Runs `write`, which may change the machine behind the program's back, and
+    /// records what it changed in the movie being recorded.
+    fn record_writes(&mut self, write: impl FnOnce(&mut Self)) {
+        let watch = self
+            .movie_recording
+            .as_ref()
+            .map(|_| movie::Watch::new(&self.machine));
+        write(self);
+        if let (Some(watch), Some(movie)) = (watch, &mut self.movie_recording) {
+            movie.record_changes(&watch, &self.machine, self.frame_cycle);
+        }
+    }
+
+    /// Applies the cheats, except during playback where the movie repeats what they
+    /// wrote.
+    fn apply_cheats(&mut self) {
+        if self.playback.is_none() && !self.cheats.cheats.is_empty() {
+            self.record_writes(|chip| chip.cheats.apply(&mut chip.machine));
+        }
+    }
+
     pub fn cycle(&mut self) {
-        let first_part = self.memory[self.pc];
-        let second_part = self.memory[self.pc + 1];
-        self.whole = (first_part as u16) << 8 | second_part as u16;
-        let disc_1: u8 = first_part.shr(4);
-        let reg_1: u8 = first_part & 0x0F;
-        let reg_2: u8 = second_part.shr(4);
-        let disc_2: u8 = second_part & 0x0F;
-        let address = ((reg_1 as u16) << 8) | (second_part as u16);
-        let number = reg_2 << 4 | disc_2;
-        self.last_pc = self.pc;
-        if !self.wainting {
-            self.pc += 2;
-            self.cycles += 1;
-        }
-        self.timers.update();
-        (self.keys, self.running) = self.screen.get_key_state();
-        match disc_1 {
-            0x00 => self.clear_return(address),
-            0x01 => self.jump_to_address(address),
-            0x02 => self.call_subroutine(address),
-            0x03 => self.skip_if_reg_equal_val(number, reg_1),
-            0x04 => self.skip_if_reg_not_equal_val(number, reg_1),
-            0x05 => self.skip_if_reg_equal_reg(reg_1, reg_2),
-            0x06 => self.move_value_to_reg(reg_1, number),
-            0x07 => self.add_value_to_reg(reg_1, number),
-            0x08 => self.execute_logical_instruction(reg_1, reg_2, disc_2),
-            0x09 => self.skip_if_reg_not_equal_reg(reg_1, reg_2),
-            0x0A => self.load_index_reg_with_value(address),
-            0x0B => self.jump_to_register_plus_value(address),
-            0x0C => self.generate_random_number(reg_1, number),
-            0x0D => self.draw_sprite(reg_1, reg_2, disc_2),
-            0x0E => self.keyboard_routines(reg_1, number),
-            0x0F => self.misc_routines(reg_1, number),
-            _ => panic!("Unsupported instruction: {:0x}", self.whole),
-        }
-        if self.wainting {
-            self.pc = self.last_pc;
-        }
-    }
-
-    pub fn info_dump(&self) {
-        let ins = disasm::disasm_chip_8_op(&self.memory, self.last_pc);
-        println!(
+        self.replay_writes();
+        #[cfg(feature = "scripting")]
+        if self.script.is_some() {
+            self.record_writes(|chip| {
+                if let Some(script) = &mut chip.script {
+                    script.before_instruction(&mut chip.machine);
+                }
+            });
+        }
+        if self.machine.fault().is_some() {
+            return;
+        }
+        self.frame_cycle += 1;
+        if let Err(fault) = self.machine.cycle(&mut self.rng) {
+            self.screen.warn(&format!("Halted: {}", fault));
+            self.screen.set_status(&format!("Halted: {}", fault));
+            if self.frontend == options::Frontend::Headless {
+                self.running = false;
+            }
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.record(&self.machine);
+        }
+        #[cfg(feature = "scripting")]
+        if self.script.is_some() {
+            self.record_writes(|chip| {
+                if let Some(script) = &mut chip.script {
+                    script.after_instruction(&mut chip.machine);
+                }
+            });
+        }
+        if self.machine.take_redraw() {
+            self.screen.update(&self.machine.display);
+        }
+    }
+
+    pub fn info_dump(&mut self) {
+        let last_pc = self.machine.last_pc();
+        let ins = disasm::disasm_chip_8_op(&self.machine.memory, last_pc);
+        self.screen.notify(&format!(
             "PC:{:04X} OP:{:04X} CYCLE:{} INS: {}",
-            self.last_pc, self.whole, self.cycles, ins
-        );
-        self.dump(&format!("memdump_{}.bin", self.cycles))
+            last_pc,
+            self.machine.opcode(),
+            self.machine.cycles(),
+            ins
+        ));
+        self.dump(&format!("memdump_{}.bin", self.machine.cycles()))
     }
 
     pub fn dump(&self, filename: &str) {
         let mut file = File::create(filename).expect("Unable to create file");
-        file.write_all(&self.memory).expect("Unable to write data");
+        file.write_all(&self.machine.memory)
+            .expect("Unable to write data");
     }
 
-    fn load_rom(&mut self, rom: &[u8], index: usize) {
-        self.memory[index..index + rom.len()].copy_from_slice(rom)
+    pub fn screenshot(&mut self, filename: &str) {
+        match self.screen.save_screenshot(Path::new(filename)) {
+            Ok(()) => self
+                .screen
+                .notify(&format!("Saved screenshot to {}", filename)),
+            Err(err) => self
+                .screen
+                .warn(&format!("Unable to save screenshot {}: {}", filename, err)),
+        }
     }
-}
 
-impl Chip8 {
-    fn clear_return(&mut self, address: u16) {
-        match address {
-            0x00E0 => self.screen.clear(),
-            0x00EE => {
-                self.sp -= 1;
-                self.pc = (self.memory[self.sp] as usize) << 8 | self.memory[self.sp - 1] as usize;
-                self.sp -= 1;
+    pub fn start_recording(&mut self) {
+        let filename = match &self.record_file {
+            Some(file) if self.recordings == 0 => file.clone(),
+            Some(file) => numbered(file, self.recordings + 1),
+            None => format!("recording_{}.gif", self.frames),
+        };
+        match self.screen.start_recording(Path::new(&filename)) {
+            Ok(recorder) => {
+                self.screen.notify(&format!("Recording to {}", filename));
+                self.recorder = Some(recorder);
+                self.recordings += 1;
             }
-            _ => self.pc = address as usize,
+            Err(err) => self
+                .screen
+                .warn(&format!("Unable to record to {}: {}", filename, err)),
         }
     }
-    fn jump_to_address(&mut self, address: u16) {
-        self.pc = address as usize;
-    }
-    fn call_subroutine(&mut self, address: u16) {
-        self.memory[self.sp] = self.pc as u8;
-        self.sp += 1;
-        self.memory[self.sp] = (self.pc.shr(8)) as u8;
-        self.sp += 1;
-        self.pc = address as usize;
-    }
-    fn skip_if_reg_equal_val(&mut self, number: u8, reg: u8) {
-        if self.registers[reg as usize] == number {
-            self.pc += 2;
+
+    pub fn stop_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            match recorder.finish() {
+                Ok(()) => self.screen.notify("Recording stopped"),
+                Err(err) => self
+                    .screen
+                    .warn(&format!("Unable to finish recording: {}", err)),
+            }
         }
     }
-    fn skip_if_reg_not_equal_val(&mut self, number: u8, reg: u8) {
-        if self.registers[reg as usize] != number {
-            self.pc += 2;
+
+    pub fn save_movie(&mut self) {
+        let (Some(movie), Some(filename)) = (self.movie_recording.take(), &self.movie_record_file)
+        else {
+            return;
+        };
+        match movie.save(Path::new(filename)) {
+            Ok(()) => self.screen.notify(&format!(
+                "Saved {} frames of input to {}",
+                movie.frames.len(),
+                filename
+            )),
+            Err(err) => self
+                .screen
+                .warn(&format!("Unable to save movie {}: {}", filename, err)),
         }
     }
-    fn skip_if_reg_equal_reg(&mut self, reg_1: u8, reg_2: u8) {
-        if self.registers[reg_1 as usize] == self.registers[reg_2 as usize] {
-            self.pc += 2;
+
+    pub fn save_profile(&mut self) {
+        let (Some(profiler), Some(dir)) = (self.profiler.take(), &self.profile_dir) else {
+            return;
+        };
+        match profiler.write_report(dir, &self.machine.memory, self.rom.len()) {
+            Ok(()) => self
+                .screen
+                .notify(&format!("Saved profile to {}", dir.display())),
+            Err(err) => self.screen.warn(&format!(
+                "Unable to save profile to {}: {}",
+                dir.display(),
+                err
+            )),
         }
     }
-    fn move_value_to_reg(&mut self, reg: u8, number: u8) {
-        self.registers[reg as usize] = number;
+
+    /// Draws the sprite region to `--sprites`.
+    pub fn save_sprites(&mut self) {
+        let Some(filename) = self.sprites_file.take() else {
+            return;
+        };
+        let region = self
+            .sprite_region
+            .clone()
+            .unwrap_or(PC_START..PC_START + self.rom.len());
+        let sheet = sprites::SpriteSheet::new(
+            &self.machine.memory,
+            region,
+            self.sprite_layout,
+            self.machine.last_sprite(),
+        );
+        match sheet.save_png(Path::new(&filename), SPRITE_SCALE, self.screen.palette()) {
+            Ok(()) => self
+                .screen
+                .notify(&format!("Saved sprites to {}", filename)),
+            Err(err) => self
+                .screen
+                .warn(&format!("Unable to save sprites to {}: {}", filename, err)),
+        }
     }
-    fn add_value_to_reg(&mut self, reg: u8, number: u8) {
-        self.registers[reg as usize] = self.registers[reg as usize].wrapping_add(number);
+
+    /// Shows the machine in the debug window, or closes it.
+    fn update_debug(&mut self) {
+        if self.debug {
+            let view = screen::debug::DebugView::new(&self.machine, self.sprite_layout);
+            self.screen.show_debug(Some(&view));
+        } else {
+            self.screen.show_debug(None);
+        }
     }
-    fn execute_logical_instruction(&mut self, reg_1: u8, reg_2: u8, disc: u8) {
-        match disc {
-            0x00 => self.registers[reg_1 as usize] = self.registers[reg_2 as usize],
-            0x01 => self.registers[reg_1 as usize] |= self.registers[reg_2 as usize],
-            0x02 => self.registers[reg_1 as usize] &= self.registers[reg_2 as usize],
-            0x03 => self.registers[reg_1 as usize] ^= self.registers[reg_2 as usize],
-            0x04 => {
-                let result =
-                    self.registers[reg_1 as usize] as u16 + self.registers[reg_2 as usize] as u16;
-                self.registers[0xF] = if result > 0xFF { 1 } else { 0 };
-                self.registers[reg_1 as usize] = result as u8;
+
+    fn end_frame(&mut self) {
+        if self.debug {
+            self.update_debug();
+        }
+        if self.screenshot_at == Some(self.frames) {
+            self.screenshot(&self.screenshot_file.clone());
+        }
+        if let Some(recorder) = &mut self.recorder {
+            let sound = self.machine.sound_on();
+            if let Err(err) = recorder.capture(self.screen.pixels(), sound) {
+                self.screen
+                    .warn(&format!("Unable to record frame: {}", err));
+                self.recorder = None;
             }
-            0x05 => {
-                self.registers[0xF] =
-                    if self.registers[reg_1 as usize] > self.registers[reg_2 as usize] {
-                        1
-                    } else {
-                        0
-                    };
-                self.registers[reg_1 as usize] =
-                    self.registers[reg_1 as usize].wrapping_sub(self.registers[reg_2 as usize]);
+        }
+    }
+
+    /// Runs the script's frame callbacks and shows what it drew.
+    fn run_frame_script(&mut self) {
+        #[cfg(feature = "scripting")]
+        if self.script.is_some() {
+            let frames = self.frames;
+            self.record_writes(|chip| {
+                if let Some(script) = &mut chip.script {
+                    script.end_frame(&mut chip.machine, frames);
+                }
+            });
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script) = &mut self.script {
+            if let Some(overlay) = script.take_overlay() {
+                self.screen.set_overlay(overlay);
             }
-            0x06 => {
-                self.registers[0xF] = self.registers[reg_1 as usize] & 0x01;
-                self.registers[reg_1 as usize] >>= 1;
+            if script.quit_requested() {
+                self.running = false;
             }
-            0x07 => {
-                self.registers[0xF] =
-                    if self.registers[reg_2 as usize] > self.registers[reg_1 as usize] {
-                        1
+        }
+    }
+
+    fn handle_hotkeys(&mut self) {
+        for hotkey in self.screen.take_hotkeys() {
+            match hotkey {
+                screen::Hotkey::Screenshot => {
+                    self.screenshot(&format!("screenshot_{}.png", self.frames))
+                }
+                screen::Hotkey::Record => {
+                    if self.recorder.is_some() {
+                        self.stop_recording();
                     } else {
-                        0
-                    };
-                self.registers[reg_1 as usize] =
-                    self.registers[reg_2 as usize] - self.registers[reg_1 as usize];
-            }
-            0x0E => {
-                self.registers[0xF] = self.registers[reg_1 as usize] & 0x80;
-                self.registers[reg_1 as usize] <<= 1;
+                        self.start_recording();
+                    }
+                }
+                screen::Hotkey::Pause => {
+                    self.paused = !self.paused;
+                    self.screen.set_sound(false);
+                    self.show_speed();
+                }
+                screen::Hotkey::FrameAdvance => {
+                    self.paused = true;
+                    self.frame_advance = true;
+                    self.show_speed();
+                }
+                screen::Hotkey::Slower => {
+                    self.speed = self.speed.saturating_sub(1);
+                    self.show_speed();
+                }
+                screen::Hotkey::Faster => {
+                    self.speed = (self.speed + 1).min(SPEEDS.len() - 1);
+                    self.show_speed();
+                }
+                screen::Hotkey::Launcher => self.open_launcher(),
+                screen::Hotkey::Reset => {
+                    if let Some(movie) = &mut self.movie_recording {
+                        movie.record(movie::Event::Reset);
+                    }
+                    self.reset();
+                }
+                screen::Hotkey::Cheats => self.open_cheat_menu(),
+                screen::Hotkey::Debug => {
+                    self.debug = !self.debug;
+                    self.update_debug();
+                }
+                screen::Hotkey::MenuInput(key) if self.cheat_menu_open => {
+                    self.cheat_menu_input(&key)
+                }
+                screen::Hotkey::MenuInput(key) => self.launcher_input(&key),
             }
-            _ => panic!("Unsupported instruction: {:0x}", self.whole),
         }
     }
-    fn skip_if_reg_not_equal_reg(&mut self, reg_1: u8, reg_2: u8) {
-        if self.registers[reg_1 as usize] != self.registers[reg_2 as usize] {
-            self.pc += 2;
+
+    /// Shows the ROM launcher in the last launcher directory, or next to the current ROM.
+    pub fn open_launcher(&mut self) {
+        let dir = self.launcher_dir.clone().unwrap_or_else(|| {
+            Path::new(&self.rom_file)
+                .parent()
+                .filter(|dir| dir.is_dir())
+                .unwrap_or(Path::new("."))
+                .to_path_buf()
+        });
+        let database = match &self.rom_db {
+            Some(rom_db) => romdb::RomDatabase::load(Path::new(rom_db)).unwrap_or_else(|err| {
+                self.screen
+                    .warn(&format!("Unable to load ROM database: {}", err));
+                romdb::RomDatabase::bundled()
+            }),
+            None => romdb::RomDatabase::bundled(),
+        };
+        match launcher::Launcher::open(&dir, database) {
+            Ok(mut launcher) => {
+                for warning in launcher.take_warnings() {
+                    self.screen.warn(&warning);
+                }
+                self.launcher = Some(launcher);
+                self.screen.set_sound(false);
+                self.show_launcher();
+            }
+            Err(err) => self.screen.warn(&format!(
+                "Unable to list ROMs in {}: {}",
+                dir.display(),
+                err
+            )),
         }
     }
-    fn load_index_reg_with_value(&mut self, address: u16) {
-        self.i = address;
+
+    fn show_launcher(&mut self) {
+        if let Some(launcher) = &self.launcher {
+            self.screen
+                .show_menu(&launcher.title(), &launcher.labels(), launcher.selected());
+        }
     }
-    fn jump_to_register_plus_value(&mut self, adress: u16) {
-        self.pc = (self.registers[0] as u16 + adress) as usize;
+
+    fn launcher_input(&mut self, key: &str) {
+        let Some(launcher) = &mut self.launcher else {
+            return;
+        };
+        let action = launcher.input(key);
+        for warning in launcher.take_warnings() {
+            self.screen.warn(&warning);
+        }
+        match action {
+            launcher::Action::None => self.show_launcher(),
+            launcher::Action::Close if self.rom.is_empty() => self.running = false,
+            launcher::Action::Close => {
+                self.launcher = None;
+                self.screen.close_menu();
+            }
+            launcher::Action::Launch(path) => match rom::load(&path.to_string_lossy()) {
+                Ok((name, rom)) => {
+                    self.launcher_dir = Some(launcher.dir().to_path_buf());
+                    self.launcher = None;
+                    let sha1 = config::rom_sha1(&rom);
+                    self.screen.close_menu();
+                    match self.load(name, rom) {
+                        Ok(()) => {
+                            if let Some(movie) = &mut self.movie_recording {
+                                movie.record(movie::Event::Rom {
+                                    sha1,
+                                    path: path.to_string_lossy().into_owned(),
+                                });
+                            }
+                        }
+                        Err(err) => {
+                            self.screen.warn(&err.to_string());
+                            self.screen.set_status(&err.to_string());
+                        }
+                    }
+                }
+                Err(err) => {
+                    self.screen.warn(&err.to_string());
+                    self.screen.set_status(&err.to_string());
+                }
+            },
+        }
     }
-    fn generate_random_number(&mut self, reg: u8, mask: u8) {
-        self.registers[reg as usize] = rand::random::<u8>() & mask;
+
+    pub fn open_cheat_menu(&mut self) {
+        if self.launcher.is_some() {
+            return;
+        }
+        if self.cheat_menu.is_none() {
+            self.cheat_menu = Some(cheats::CheatMenu::new(&self.machine));
+        }
+        self.cheat_menu_open = true;
+        self.screen.set_sound(false);
+        self.show_cheat_menu();
     }
-    fn draw_sprite(&mut self, x: u8, y: u8, len: u8) {
-        let sprite = &self.memory[self.i as usize..((self.i as usize) + len as usize)];
-        let x = self.registers[x as usize];
-        let y = self.registers[y as usize];
-        let collision = self.screen.draw(x, y, sprite);
-        self.registers[0xF] = if collision { 1 } else { 0 };
+
+    fn show_cheat_menu(&mut self) {
+        if let Some(menu) = &self.cheat_menu {
+            self.screen.show_menu(
+                &menu.title(),
+                &menu.labels(&self.cheats, &self.machine),
+                menu.selected(),
+            );
+        }
     }
-    fn keyboard_routines(&mut self, reg: u8, disc: u8) {
-        match disc {
-            0x9E => {
-                if self.keys.contains(&self.registers[reg as usize]) {
-                    self.pc += 2;
-                }
-            }
-            0xA1 => {
-                if !self.keys.contains(&self.registers[reg as usize]) {
-                    self.pc += 2;
-                }
+
+    fn cheat_menu_input(&mut self, key: &str) {
+        let Some(menu) = &mut self.cheat_menu else {
+            return;
+        };
+        match menu.input(key, &mut self.cheats, &self.machine) {
+            cheats::Action::None => self.show_cheat_menu(),
+            cheats::Action::Save => {
+                self.save_cheats();
+                self.show_cheat_menu();
             }
-            _ => panic!("Unsupported instruction"),
-        }
-    }
-    fn misc_routines(&mut self, reg: u8, disc: u8) {
-        match disc {
-            0x07 => self.registers[reg as usize] = self.timers.delay_timer,
-            0x0A => {
-                if let Some(key) = self.keys.iter().next() {
-                    self.wainting = false;
-                    self.registers[reg as usize] = *key;
-                } else {
-                    self.wainting = true;
-                }
+            cheats::Action::Close => {
+                self.cheat_menu_open = false;
+                self.screen.close_menu();
             }
-            0x15 => self.timers.delay_timer = self.registers[reg as usize],
-            0x18 => self.timers.sound_timer = self.registers[reg as usize],
-            0x1E => self.i = self.i.wrapping_add(self.registers[reg as usize] as u16),
-            0x29 => self.i = self.registers[reg as usize] as u16 * 5,
-            0x33 => {
-                let value = self.registers[reg as usize];
-                self.memory[self.i as usize] = value / 100;
-                self.memory[self.i as usize + 1] = (value / 10) % 10;
-                self.memory[self.i as usize + 2] = value % 10;
+        }
+    }
+
+    pub fn save_cheats(&mut self) {
+        let sha1 = config::rom_sha1(&self.rom);
+        let Some(path) = self
+            .cheat_file
+            .clone()
+            .or_else(|| Some(cheats::default_path(&self.options.config_dir()?, &sha1)))
+        else {
+            self.screen
+                .warn("Unable to save cheats: no config directory");
+            return;
+        };
+        match self.cheats.save(&path, &sha1) {
+            Ok(()) => {
+                self.screen
+                    .notify(&format!("Saved cheats to {}", path.display()));
+                self.screen.set_status("Cheats saved");
             }
-            0x55 => {
-                for i in 0..=reg {
-                    self.memory[self.i as usize + i as usize] = self.registers[i as usize];
-                }
-                self.i += reg as u16 + 1;
+            Err(err) => self.screen.warn(&format!(
+                "Unable to save cheats to {}: {}",
+                path.display(),
+                err
+            )),
+        }
+    }
+
+    /// Soft reset: puts registers, stack, timers, keys and the screen back in their
+    /// power-on state and copies the loaded ROM into fresh memory.
+    pub fn reset(&mut self) {
+        if let Err(err) = self.machine.reset(&self.rom) {
+            self.screen.warn(&err.to_string());
+        }
+        if let Some(profiler) = &mut self.profiler {
+            profiler.restart();
+        }
+        self.screen.set_sound(false);
+        self.screen.update(&self.machine.display);
+    }
+
+    /// Hard reset: swaps in the ROM `name` with the settings the command line, config
+    /// file and ROM database give it, as at startup, and resets. Forgets any RAM search.
+    pub fn load(&mut self, name: String, rom: Vec<u8>) -> Result<(), Box<dyn Error>> {
+        rom::validate(&name, &rom)?;
+        self.options.file = name;
+        self.options.rom = rom;
+        // `--cheats` belongs to the ROM given on the command line.
+        self.options.cheat_file = None;
+        self.options.resolve_rom()?;
+        self.rom_file = self.options.file.clone();
+        self.rom = std::mem::take(&mut self.options.rom);
+        self.tickrate = self.options.tickrate;
+        self.machine.quirks = self.options.quirks;
+        self.screen.set_palette(self.options.palette);
+        self.screen.set_keymap(self.options.keymap.clone());
+        self.cheats = std::mem::take(&mut self.options.cheats);
+        self.cheat_file = None;
+        self.cheat_menu = None;
+        let title = self.options.title.clone().unwrap_or_else(|| {
+            Path::new(&self.rom_file)
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        });
+        self.screen.set_title(&title);
+        self.reset();
+        Ok(())
+    }
+
+    fn show_speed(&mut self) {
+        let status = match SPEEDS[self.speed] {
+            _ if self.paused => format!("Paused at frame {}", self.frames),
+            Some(1.0) => String::new(),
+            Some(speed) => format!("{}x", speed),
+            None => "Fast forward".to_string(),
+        };
+        self.screen.set_status(&status);
+    }
+}
+
+impl Drop for Chip8 {
+    fn drop(&mut self) {
+        self.stop_recording();
+        self.save_movie();
+        self.save_profile();
+        self.save_sprites();
+    }
+}
+
+/// `path` with `-number` put before its extension, e.g. `demo-2.gif`.
+fn numbered(path: &str, number: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::{env, fs, process};
+
+    use clap::Parser;
+
+    use super::*;
+
+    /// Options for a headless run of `rom` that leaves the user's config alone.
+    fn headless_options(rom: Vec<u8>) -> options::Chip8Options {
+        let args = ["chip_8", "--file", "test.ch8", "--frontend", "headless"];
+        let mut options = options::Chip8Options::parse_from(args);
+        options.rom = rom;
+        let config_dir = format!("chip_8-no-config-{}", process::id());
+        options.config_dir = Some(env::temp_dir().join(config_dir));
+        options
+    }
+
+    /// Holds key 1 on every other frame and presses F4 once.
+    struct Player {
+        frame: usize,
+    }
+
+    impl screen::Backend for Player {
+        fn present(&mut self, _pixels: &[u8], _width: u32, _height: u32) {}
+
+        fn poll_events(&mut self) -> Vec<screen::HostEvent> {
+            self.frame += 1;
+            if self.frame == 4 {
+                vec![screen::HostEvent::KeyDown("f4".to_string())]
+            } else {
+                Vec::new()
             }
-            0x65 => {
-                for i in 0..=reg {
-                    self.registers[i as usize] = self.memory[self.i as usize + i as usize];
-                }
-                self.i += reg as u16 + 1;
+        }
+
+        fn held_keys(&mut self) -> Vec<String> {
+            if self.frame % 2 == 1 {
+                vec!["1".to_string()]
+            } else {
+                Vec::new()
             }
-            _ => panic!("Unsupported instruction: {:0x}", self.whole),
         }
+
+        fn set_status(&mut self, _status: &str) {}
+    }
+
+    fn cheat_list(chip: &Chip8) -> Vec<(u16, u8)> {
+        let cheats = chip.cheats.cheats.iter();
+        cheats.map(|cheat| (cheat.address, cheat.value)).collect()
+    }
+
+    #[test]
+    fn load_swaps_the_rom_and_its_cheats() {
+        let config_dir = env::temp_dir().join(format!("chip_8-load-{}", process::id()));
+        let first = vec![0x12, 0x00];
+        let second = vec![0x60, 0x01, 0x12, 0x02];
+        let second_sha1 = config::rom_sha1(&second);
+        let mut saved = cheats::CheatList::default();
+        saved.set(0x300, 0x42);
+        saved
+            .save(
+                &cheats::default_path(&config_dir, &second_sha1),
+                &second_sha1,
+            )
+            .unwrap();
+
+        let mut options = headless_options(first.clone());
+        options.config_dir = Some(config_dir.clone());
+        let mut chip = Chip8::new_with_rom(options).unwrap();
+        chip.cheats.set(0x301, 7);
+        chip.cycle();
+        chip.load("second.ch8".to_string(), second.clone()).unwrap();
+        assert_eq!(chip.rom, second);
+        assert_eq!(chip.machine.memory[0x200..0x204], second[..]);
+        assert_eq!(chip.machine.pc, 0x200);
+        assert_eq!(cheat_list(&chip), [(0x300, 0x42)]);
+
+        chip.load("first.ch8".to_string(), first.clone()).unwrap();
+        assert_eq!(chip.machine.memory[0x200..0x204], [0x12, 0x00, 0, 0]);
+        assert!(cheat_list(&chip).is_empty());
+        assert!(chip.load("empty.ch8".to_string(), Vec::new()).is_err());
+        assert_eq!(chip.rom, first);
+        fs::remove_dir_all(config_dir).unwrap();
+    }
+
+    #[test]
+    fn recordings_after_the_first_are_numbered() {
+        let dir = env::temp_dir().join(format!("chip_8-record-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let mut options = headless_options(vec![0x12, 0x00]);
+        options.record_file = Some(dir.join("demo.gif").to_string_lossy().into_owned());
+        let mut chip = Chip8::new_with_rom(options).unwrap();
+        chip.stop_recording();
+        chip.start_recording();
+        chip.stop_recording();
+        chip.start_recording();
+        chip.stop_recording();
+        let mut files: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        files.sort();
+        fs::remove_dir_all(dir).unwrap();
+        assert_eq!(files, ["demo-2.gif", "demo-3.gif", "demo.gif"]);
+        assert_eq!(numbered("frames", 2), "frames-2");
+    }
+
+    #[test]
+    fn load_resolves_the_settings_of_the_new_rom() {
+        let dir = env::temp_dir().join(format!("chip_8-resolve-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let config_file = dir.join("config.toml");
+        let rom = vec![0x12, 0x00];
+        fs::write(
+            &config_file,
+            format!(
+                "[rom.{}]\ntickrate = 25\npalette = \"102030,405060\"\nquirks = [\"vblank\"]\n",
+                config::rom_sha1(&rom)
+            ),
+        )
+        .unwrap();
+        let mut options = headless_options(vec![0x12, 0x02]);
+        options.config_file = Some(config_file.to_string_lossy().into_owned());
+        let mut chip = Chip8::new_with_rom(options).unwrap();
+        assert_eq!(chip.tickrate, 10);
+
+        chip.load("game.ch8".to_string(), rom).unwrap();
+        assert_eq!(chip.tickrate, 25);
+        assert!(chip.machine.quirks.vblank);
+        assert_eq!(
+            chip.screen.palette(),
+            &[[0x10, 0x20, 0x30], [0x40, 0x50, 0x60]]
+        );
+        chip.load("other.ch8".to_string(), vec![0x12, 0x04])
+            .unwrap();
+        assert_eq!(chip.tickrate, 10);
+        assert!(!chip.machine.quirks.vblank);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    /// Keeps what the emulator tells the user.
+    struct Messages(Rc<RefCell<Vec<String>>>);
+
+    impl screen::Backend for Messages {
+        fn present(&mut self, _pixels: &[u8], _width: u32, _height: u32) {}
+
+        fn poll_events(&mut self) -> Vec<screen::HostEvent> {
+            Vec::new()
+        }
+
+        fn held_keys(&mut self) -> Vec<String> {
+            Vec::new()
+        }
+
+        fn set_status(&mut self, _status: &str) {}
+
+        fn notify(&mut self, message: &str) {
+            self.0.borrow_mut().push(message.to_string());
+        }
+
+        fn warn(&mut self, message: &str) {
+            self.0.borrow_mut().push(format!("warning: {}", message));
+        }
+    }
+
+    #[test]
+    fn messages_go_through_the_backend() {
+        let messages = Rc::new(RefCell::new(Vec::new()));
+        let backend = Box::new(Messages(messages.clone()));
+        let mut chip = Chip8::with_backend(headless_options(vec![0x12, 0x00]), backend);
+        let path = env::temp_dir().join(format!("chip_8-message-{}.png", process::id()));
+        let path = path.to_string_lossy().into_owned();
+        chip.screenshot(&path);
+        fs::remove_file(&path).unwrap();
+        chip.screenshot("/nonexistent/screenshot.png");
+        let messages = messages.borrow();
+        assert_eq!(messages[0], format!("Saved screenshot to {}", path));
+        assert!(messages[1].starts_with("warning: Unable to save screenshot"));
+    }
+
+    #[test]
+    fn a_recorded_movie_replays_keys_resets_and_cheats() {
+        let rom = vec![
+            0xA3, 0x00, // I = 0x300
+            0xF0, 0x65, // V0 = [I], where the cheat writes
+            0xC1, 0xFF, // V1 = random
+            0x63, 0x01, // V3 = 1
+            0xE3, 0xA1, // skip unless key 1 is held
+            0x72, 0x01, // V2 += 1
+            0x12, 0x00, // loop
+        ];
+        let movie_file = env::temp_dir().join(format!("chip_8-replay-{}.txt", process::id()));
+        let mut options = headless_options(rom.clone());
+        options.seed = Some(5);
+        options.movie_record_file = Some(movie_file.to_string_lossy().into_owned());
+        let mut chip = Chip8::with_backend(options, Box::new(Player { frame: 0 }));
+        chip.cheats.set(0x300, 0x42);
+        for _ in 0..8 {
+            chip.frame();
+        }
+        let recorded = chip.machine.save_state();
+        drop(chip);
+
+        let movie = movie::Movie::load(&movie_file).unwrap();
+        fs::remove_file(&movie_file).unwrap();
+        assert_eq!(movie.frames.len(), 8);
+        assert_eq!(movie.rom_sha1, config::rom_sha1(&rom));
+        assert!(movie
+            .events_at(3)
+            .any(|event| *event == movie::Event::Reset));
+        let mut options = headless_options(rom);
+        options.seed = Some(movie.seed);
+        options.movie = Some(movie);
+        let mut chip = Chip8::new_with_rom(options).unwrap();
+        for _ in 0..8 {
+            chip.frame();
+        }
+        assert_eq!(chip.machine.registers[0], 0x42);
+        assert_eq!(chip.machine.save_state(), recorded);
     }
 }
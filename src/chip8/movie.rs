@@ -0,0 +1,321 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::options::Quirks;
+use crate::machine::{Machine, MEMORY_SIZE};
+
+const HEADER: &str = "CHIP8MOVIE 2";
+
+/// Something besides keypad input that a replay has to repeat.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    /// The machine was reset.
+    Reset,
+    /// The ROM at `path` was loaded instead; replay checks it still has this SHA-1.
+    Rom { sha1: String, path: String },
+    /// A cheat or script wrote `value` to memory before instruction `cycle` of the
+    /// frame, or after the last one when `cycle` is the tickrate.
+    Poke { cycle: u32, address: u16, value: u8 },
+    /// A script set register V`register`, timed like `Poke`.
+    Register { cycle: u32, register: u8, value: u8 },
+    /// A script set I, timed like `Poke`.
+    Index { cycle: u32, value: u16 },
+}
+
+impl Event {
+    /// The instruction a write happens before; `None` for resets and ROM changes,
+    /// which happen before the frame.
+    pub fn cycle(&self) -> Option<u32> {
+        match self {
+            Event::Reset | Event::Rom { .. } => None,
+            Event::Poke { cycle, .. }
+            | Event::Register { cycle, .. }
+            | Event::Index { cycle, .. } => Some(*cycle),
+        }
+    }
+
+    /// Repeats a write on `machine`; resets and ROM changes are left to the caller.
+    pub fn apply(&self, machine: &mut Machine) {
+        match *self {
+            Event::Reset | Event::Rom { .. } => {}
+            Event::Poke { address, value, .. } => machine.memory[address as usize] = value,
+            Event::Register {
+                register, value, ..
+            } => machine.registers[register as usize] = value,
+            Event::Index { value, .. } => machine.i = value,
+        }
+    }
+
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.split(' ');
+        let name = fields.next()?;
+        let mut number = |radix| -> Option<u32> { u32::from_str_radix(fields.next()?, radix).ok() };
+        let event = match name {
+            "reset" => Event::Reset,
+            "rom" => {
+                let (sha1, path) = line.strip_prefix("rom ")?.split_once(' ')?;
+                return Some(Event::Rom {
+                    sha1: sha1.to_string(),
+                    path: path.to_string(),
+                });
+            }
+            "poke" => Event::Poke {
+                cycle: number(10)?,
+                address: u16::try_from(number(16)?)
+                    .ok()
+                    .filter(|&a| (a as usize) < MEMORY_SIZE)?,
+                value: u8::try_from(number(16)?).ok()?,
+            },
+            "reg" => Event::Register {
+                cycle: number(10)?,
+                register: u8::try_from(number(16)?).ok().filter(|&r| r < 16)?,
+                value: u8::try_from(number(16)?).ok()?,
+            },
+            "index" => Event::Index {
+                cycle: number(10)?,
+                value: u16::try_from(number(16)?).ok()?,
+            },
+            _ => return None,
+        };
+        fields.next().is_none().then_some(event)
+    }
+
+    fn save(&self) -> String {
+        match self {
+            Event::Reset => "reset".to_string(),
+            Event::Rom { sha1, path } => format!("rom {} {}", sha1, path),
+            Event::Poke {
+                cycle,
+                address,
+                value,
+            } => format!("poke {} {:03X} {:02X}", cycle, address, value),
+            Event::Register {
+                cycle,
+                register,
+                value,
+            } => format!("reg {} {:X} {:02X}", cycle, register, value),
+            Event::Index { cycle, value } => format!("index {} {:03X}", cycle, value),
+        }
+    }
+}
+
+/// Per-frame keypad input plus everything else needed to replay a session exactly.
+///
+/// Stored as text: a header line, `seed`, `tickrate`, `quirks` and `rom` (the SHA-1
+/// of the ROM it starts with) lines, then one line per frame holding the held keys
+/// as a 16-bit hex mask (bit N = key N). The events of a frame come on the lines
+/// before it: `reset`, `rom SHA1 PATH`, `poke CYCLE ADDR VALUE`, `reg CYCLE N VALUE`
+/// and `index CYCLE VALUE`, with addresses and values in hex.
+pub struct Movie {
+    pub seed: u64,
+    pub tickrate: u32,
+    pub quirks: Quirks,
+    pub rom_sha1: String,
+    pub frames: Vec<u16>,
+    /// Events with the index of the frame they happen in, in order.
+    pub events: Vec<(usize, Event)>,
+}
+
+impl Movie {
+    pub fn new(seed: u64, tickrate: u32, quirks: Quirks, rom_sha1: String) -> Self {
+        Self {
+            seed,
+            tickrate,
+            quirks,
+            rom_sha1,
+            frames: Vec::new(),
+            events: Vec::new(),
+        }
+    }
+
+    /// Adds an event to the frame being recorded, the one pushed next.
+    pub fn record(&mut self, event: Event) {
+        self.events.push((self.frames.len(), event));
+    }
+
+    /// Records the writes that turned `watch` into `machine`, at instruction `cycle`.
+    pub fn record_changes(&mut self, watch: &Watch, machine: &Machine, cycle: u32) {
+        for event in watch.changes(machine, cycle) {
+            self.record(event);
+        }
+    }
+
+    /// The events of frame `frame`.
+    pub fn events_at(&self, frame: usize) -> impl Iterator<Item = &Event> {
+        let start = self.events.partition_point(|(at, _)| *at < frame);
+        self.events[start..]
+            .iter()
+            .take_while(move |(at, _)| *at == frame)
+            .map(|(_, event)| event)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let invalid = |message: String| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", path.display(), message),
+            )
+        };
+        let contents = fs::read_to_string(path)?;
+        let mut lines = contents.lines();
+        if lines.next() != Some(HEADER) {
+            return Err(invalid("not a CHIP-8 movie".to_string()));
+        }
+
+        let mut movie = Movie::new(0, 0, Quirks::default(), String::new());
+        for line in lines.by_ref() {
+            let (name, value) = line.split_once(' ').unwrap_or((line, ""));
+            match name {
+                "seed" => movie.seed = value.parse().map_err(|_| invalid(line.to_string()))?,
+                "tickrate" => {
+                    movie.tickrate = value.parse().map_err(|_| invalid(line.to_string()))?
+                }
+                "quirks" => movie.quirks = value.parse().map_err(invalid)?,
+                "rom" => movie.rom_sha1 = value.to_string(),
+                "frames" => break,
+                _ => return Err(invalid(format!("unknown field `{}`", name))),
+            }
+        }
+        for line in lines {
+            if let Ok(mask) = u16::from_str_radix(line.trim(), 16) {
+                movie.frames.push(mask);
+            } else {
+                let event =
+                    Event::parse(line).ok_or_else(|| invalid(format!("bad line `{}`", line)))?;
+                movie.record(event);
+            }
+        }
+        Ok(movie)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut contents = format!(
+            "{}\nseed {}\ntickrate {}\nquirks {}\nrom {}\nframes\n",
+            HEADER, self.seed, self.tickrate, self.quirks, self.rom_sha1
+        );
+        let mut events = self.events.iter().peekable();
+        for frame in 0..=self.frames.len() {
+            while let Some((_, event)) = events.next_if(|(at, _)| *at == frame) {
+                contents.push_str(&event.save());
+                contents.push('\n');
+            }
+            if let Some(mask) = self.frames.get(frame) {
+                contents.push_str(&format!("{:04X}\n", mask));
+            }
+        }
+        fs::write(path, contents)
+    }
+}
+
+/// Memory, registers and I from before a cheat or script ran, to record what it
+/// changed.
+pub struct Watch {
+    memory: [u8; MEMORY_SIZE],
+    registers: [u8; 16],
+    i: u16,
+}
+
+impl Watch {
+    pub fn new(machine: &Machine) -> Self {
+        Watch {
+            memory: machine.memory,
+            registers: machine.registers,
+            i: machine.i,
+        }
+    }
+
+    /// The writes that turned the watched state into `machine`'s, as events at `cycle`.
+    pub fn changes(&self, machine: &Machine, cycle: u32) -> Vec<Event> {
+        let pokes = (0..MEMORY_SIZE)
+            .filter(|&address| machine.memory[address] != self.memory[address])
+            .map(|address| Event::Poke {
+                cycle,
+                address: address as u16,
+                value: machine.memory[address],
+            });
+        let registers = (0..16)
+            .filter(|&register| machine.registers[register] != self.registers[register])
+            .map(|register| Event::Register {
+                cycle,
+                register: register as u8,
+                value: machine.registers[register],
+            });
+        let index = (machine.i != self.i).then_some(Event::Index {
+            cycle,
+            value: machine.i,
+        });
+        pokes.chain(registers).chain(index).collect()
+    }
+}
+
+pub fn keys_to_mask(keys: &HashSet<u8>) -> u16 {
+    keys.iter().fold(0, |mask, key| mask | 1 << key)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, process};
+
+    use super::*;
+
+    #[test]
+    fn events_survive_saving_and_loading() {
+        let mut movie = Movie::new(7, 12, Quirks::default(), "ab".repeat(20));
+        movie.record(Event::Poke {
+            cycle: 0,
+            address: 0x300,
+            value: 0x42,
+        });
+        movie.frames.push(0x0001);
+        movie.record(Event::Reset);
+        movie.record(Event::Rom {
+            sha1: "cd".repeat(20),
+            path: "games/pong 2.ch8".to_string(),
+        });
+        movie.record(Event::Register {
+            cycle: 3,
+            register: 0xA,
+            value: 5,
+        });
+        movie.record(Event::Index {
+            cycle: 12,
+            value: 0x2F0,
+        });
+        movie.frames.push(0x8000);
+
+        let path = env::temp_dir().join(format!("chip_8-movie-{}.txt", process::id()));
+        movie.save(&path).unwrap();
+        let loaded = Movie::load(&path).unwrap();
+        fs::remove_file(path).unwrap();
+        assert_eq!((loaded.seed, loaded.tickrate), (7, 12));
+        assert_eq!(loaded.rom_sha1, movie.rom_sha1);
+        assert_eq!(loaded.frames, movie.frames);
+        assert_eq!(loaded.events, movie.events);
+        assert_eq!(loaded.events_at(1).count(), 4);
+    }
+
+    #[test]
+    fn watch_reports_what_changed() {
+        let mut machine = Machine::new(Quirks::default());
+        let watch = Watch::new(&machine);
+        machine.memory[0x300] = 9;
+        machine.registers[2] = 1;
+        assert_eq!(
+            watch.changes(&machine, 4),
+            [
+                Event::Poke {
+                    cycle: 4,
+                    address: 0x300,
+                    value: 9
+                },
+                Event::Register {
+                    cycle: 4,
+                    register: 2,
+                    value: 1
+                },
+            ]
+        );
+    }
+}
@@ -1,19 +1,405 @@
-use clap::Parser;
+use std::error::Error;
+use std::io;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
 
+use clap::{Args, Parser, ValueEnum};
+
+use super::cheats::{self, CheatList};
+use super::config::{self, Config, Settings};
+use super::keymap::{self, Binding, Keymap, Layout};
+use super::movie::Movie;
+use super::rom;
+use super::romdb::{RomDatabase, RomInfo};
+use super::screen::{self, Palette};
+#[cfg(feature = "scripting")]
+use super::script::Script;
+use super::sprites::{self, SpriteLayout};
+use crate::emulator::DEFAULT_TICKRATE;
+
+pub use crate::machine::Quirks;
+
+const DEFAULT_SCALE: u32 = 10;
+const DEFAULT_VOLUME: u8 = 25;
+
+/// The default is SDL when built with the `sdl` feature, the terminal otherwise.
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum Frontend {
+    /// Window rendered with SDL2.
+    #[cfg(feature = "sdl")]
+    #[default]
+    Sdl,
+    /// ANSI/Unicode output in the terminal, for use over SSH.
+    #[cfg_attr(not(feature = "sdl"), default)]
+    Tty,
+    /// No output or input at all; runs as fast as possible.
+    Headless,
+}
+
+/// The `--quirk-*` switches, turned into `Quirks` by `Chip8Options::build`.
+#[derive(Args, Clone, Copy, Default)]
+pub struct QuirkArgs {
+    /// FX0A resumes as soon as a key goes down instead of waiting for its release.
+    #[arg(long = "quirk-key-wait-press")]
+    pub key_wait_press: bool,
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    #[arg(long = "quirk-shift")]
+    pub shift: bool,
+    /// FX55 and FX65 leave I at I + X instead of I + X + 1.
+    #[arg(long = "quirk-memory-increment-by-x")]
+    pub memory_increment_by_x: bool,
+    /// FX55 and FX65 leave I unchanged.
+    #[arg(long = "quirk-memory-leave-i-unchanged")]
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the display instead of being clipped.
+    #[arg(long = "quirk-wrap")]
+    pub wrap: bool,
+    /// BNNN jumps to NNN + VX instead of NNN + V0.
+    #[arg(long = "quirk-jump")]
+    pub jump: bool,
+    /// DXYN draws at most one sprite per frame, like the COSMAC VIP.
+    #[arg(long = "quirk-vblank")]
+    pub vblank: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    #[arg(long = "quirk-logic")]
+    pub logic: bool,
+}
+
+impl From<QuirkArgs> for Quirks {
+    fn from(args: QuirkArgs) -> Self {
+        Quirks {
+            key_wait_press: args.key_wait_press,
+            shift: args.shift,
+            memory_increment_by_x: args.memory_increment_by_x,
+            memory_leave_i_unchanged: args.memory_leave_i_unchanged,
+            wrap: args.wrap,
+            jump: args.jump,
+            vblank: args.vblank,
+            logic: args.logic,
+        }
+    }
+}
+
+/// The command line. Settings the config file and ROM database can also give are read
+/// into private fields, and `build` resolves them into the public ones, so options
+/// given on the command line always win however the options were parsed.
 #[derive(Parser)]
+#[command(about = "A CHIP-8 interpreter with SDL, terminal and headless frontends.")]
 pub struct Chip8Options {
-    #[arg(long = "scale", default_value = "10")]
+    /// Screen pixels per CHIP-8 pixel [default: 10].
+    #[arg(long = "scale", value_name = "SCALE")]
+    scale_arg: Option<u32>,
+    #[arg(skip = DEFAULT_SCALE)]
     pub scale_factor: u32,
+    /// ROM to run, `-` to read it from stdin, or a zip archive as `pack.zip`
+    /// (to pick from its ROMs) or `pack.zip:path/inside.ch8`. A directory opens the launcher.
     #[arg(long = "file", value_hint = clap::ValueHint::FilePath)]
     pub file: String,
+    #[arg(long = "frontend", value_enum, default_value_t = Frontend::default())]
+    pub frontend: Frontend,
+    /// Background and foreground colors as `RRGGBB,RRGGBB` [default: 000000,ffffff].
+    #[arg(long = "palette", value_name = "PALETTE", value_parser = screen::parse_palette)]
+    palette_arg: Option<Palette>,
+    #[arg(skip = screen::DEFAULT_PALETTE)]
+    pub palette: Palette,
+    /// Beeper volume from 0 (off) to 100 [default: 25].
+    #[arg(long = "volume", value_name = "VOLUME")]
+    volume_arg: Option<u8>,
+    #[arg(skip = DEFAULT_VOLUME)]
+    pub volume: u8,
+    /// Directory holding `config.toml` and the saved cheats, instead of
+    /// `$XDG_CONFIG_HOME/chip_8`.
+    #[arg(long = "config-dir", value_hint = clap::ValueHint::DirPath)]
+    pub config_dir: Option<PathBuf>,
+    /// Config file to use instead of `config.toml` in the config directory.
+    #[arg(long = "config", value_hint = clap::ValueHint::FilePath)]
+    pub config_file: Option<String>,
+    /// Directory with a chip-8-database `sha1-hashes.json` and `programs.json`
+    /// to use instead of the bundled copy.
+    #[arg(long = "rom-db", value_hint = clap::ValueHint::DirPath)]
+    pub rom_db: Option<String>,
+    /// Save a screenshot once this many frames have been shown.
+    #[arg(long = "screenshot-at")]
+    pub screenshot_at: Option<usize>,
+    /// Where `--screenshot-at` writes to; `.png` or `.pbm`.
+    #[arg(long = "screenshot", default_value = "screenshot.png")]
+    pub screenshot_file: String,
+    /// Record gameplay to a `.gif`, or to a directory of PPM frames plus WAV audio.
+    #[arg(long = "record")]
+    pub record_file: Option<String>,
+    /// Host keyboard layout the default keypad mapping is laid out for [default: qwerty].
+    #[arg(long = "layout", value_name = "LAYOUT", value_enum)]
+    layout_arg: Option<Layout>,
+    #[arg(skip)]
+    pub layout: Layout,
+    /// Keymap file with `HEX = key, key` lines and optional `[rom.<sha1>]` sections.
+    /// Controller inputs are named `pad a`, `pad dpup`, `pad leftx-` and so on.
+    #[arg(long = "keymap", value_hint = clap::ValueHint::FilePath)]
+    pub keymap_file: Option<String>,
+    /// Bind host keys to a CHIP-8 key, e.g. `--bind 5=w,up`. Repeatable.
+    #[arg(long = "bind", value_parser = keymap::parse_binding)]
+    pub bindings: Vec<Binding>,
+    /// Instructions executed per 60 Hz frame [default: 10].
+    #[arg(long = "tickrate", value_name = "TICKRATE")]
+    tickrate_arg: Option<u32>,
+    #[arg(skip = DEFAULT_TICKRATE)]
+    pub tickrate: u32,
+    /// Seed for the random number generator (CXNN); random when not given.
+    #[arg(long = "seed")]
+    pub seed: Option<u64>,
+    /// Stop after this many frames.
+    #[arg(long = "frames")]
+    pub frame_limit: Option<usize>,
+    /// Record the keypad input of every frame to a movie file.
+    #[arg(long = "movie-record", value_hint = clap::ValueHint::FilePath)]
+    pub movie_record_file: Option<String>,
+    /// Replay a movie file; its seed, tickrate and quirks replace the ones given here.
+    /// It repeats what cheats and scripts wrote, so neither runs during playback.
+    #[arg(long = "movie-play", value_hint = clap::ValueHint::FilePath)]
+    pub movie_play_file: Option<String>,
+    /// Profile the ROM and write a report, annotated disassembly and folded call
+    /// stacks to this directory on exit.
+    #[arg(long = "profile", value_hint = clap::ValueHint::DirPath)]
+    pub profile_dir: Option<String>,
+    /// Draw memory as sprites to this PNG on exit, with the bytes of the last DXYN
+    /// highlighted, to find and document a ROM's graphics.
+    #[arg(long = "sprites", value_hint = clap::ValueHint::FilePath)]
+    pub sprites_file: Option<String>,
+    /// Hex address range drawn by `--sprites`, end excluded, like `200-400`; the ROM
+    /// when not given.
+    #[arg(long = "sprite-region", value_parser = sprites::parse_region)]
+    pub sprite_region: Option<Range<usize>>,
+    /// How `--sprites` and the debug window (F11) arrange bytes into sprites.
+    #[arg(long = "sprite-layout", value_enum, default_value = "chip8")]
+    pub sprite_layout: SpriteLayout,
+    /// Cheat file of `ADDR=VALUE` hex lines to use instead of the one kept for the ROM
+    /// in `cheats/` in the config directory; the cheat menu (F9) saves to it too.
+    #[arg(long = "cheats", value_hint = clap::ValueHint::FilePath, conflicts_with = "movie_play_file")]
+    pub cheat_file: Option<String>,
+    /// Rhai script to run alongside the ROM, see `Script` for what it can do.
+    #[cfg(feature = "scripting")]
+    #[arg(long = "script", value_hint = clap::ValueHint::FilePath, conflicts_with = "movie_play_file")]
+    pub script_file: Option<String>,
+    #[command(flatten)]
+    pub quirk_args: QuirkArgs,
+    /// Quirks from the `--quirk-*` switches, the config file and the ROM database.
+    #[arg(skip)]
+    pub quirks: Quirks,
+    #[arg(skip)]
+    pub movie: Option<Movie>,
+    #[arg(skip)]
+    pub cheats: CheatList,
+    #[cfg(feature = "scripting")]
+    #[arg(skip)]
+    pub script: Option<Script>,
+    #[arg(skip)]
+    pub keymap: Keymap,
+    /// Title of the ROM, if it is in the ROM database.
+    #[arg(skip)]
+    pub title: Option<String>,
+    /// Set when `--file` is a directory, to start in the launcher.
+    #[arg(skip)]
+    pub launcher_dir: Option<String>,
+    #[arg(skip)]
     pub rom: Vec<u8>,
 }
 
 impl Chip8Options {
-    pub fn build(&mut self) {
-        use std::fs::File;
-        use std::io::Read;
-        let mut file = File::open(&self.file).unwrap();
-        file.read_to_end(&mut self.rom).unwrap();
+    /// Loads the ROM and everything the options point at, resolving the final settings.
+    pub fn build(&mut self) -> Result<(), Box<dyn Error>> {
+        if Path::new(&self.file).is_dir() {
+            self.launcher_dir = Some(self.file.clone());
+        } else {
+            (self.file, self.rom) = rom::load(&self.file)?;
+        }
+        if let Some(movie_file) = &self.movie_play_file {
+            let movie = Movie::load(Path::new(movie_file))?;
+            let sha1 = config::rom_sha1(&self.rom);
+            if movie.rom_sha1 != sha1 {
+                return Err(format!(
+                    "{} was recorded with the ROM with SHA-1 {}, not {}",
+                    movie_file, movie.rom_sha1, sha1
+                )
+                .into());
+            }
+            self.movie = Some(movie);
+        }
+        self.resolve_rom()?;
+        if let Some(movie) = &self.movie {
+            self.seed = Some(movie.seed);
+            self.tickrate = movie.tickrate;
+            self.quirks = movie.quirks;
+        }
+        #[cfg(feature = "scripting")]
+        if let Some(script_file) = &self.script_file {
+            self.script = Some(Script::load(Path::new(script_file))?);
+        }
+        Ok(())
+    }
+
+    /// `--config-dir`, or the usual config directory.
+    pub fn config_dir(&self) -> Option<PathBuf> {
+        self.config_dir.clone().or_else(config::dir)
+    }
+
+    /// Resolves the settings of `rom` from the command line, the config file and the
+    /// ROM database: tickrate, palette, quirks, keymap, cheats and title. The launcher
+    /// runs it again for every ROM it starts.
+    pub fn resolve_rom(&mut self) -> Result<(), Box<dyn Error>> {
+        let config_file = self
+            .config_file
+            .as_ref()
+            .map(Into::into)
+            .or_else(|| Some(self.config_dir()?.join("config.toml")));
+        let config = match config_file {
+            Some(path) if self.config_file.is_some() || path.exists() => Config::load(&path)?,
+            _ => Config::default(),
+        };
+        let sha1 = config::rom_sha1(&self.rom);
+        let database = match &self.rom_db {
+            Some(dir) => RomDatabase::load(Path::new(dir))?,
+            None => RomDatabase::bundled(),
+        };
+        let info = database.lookup(&sha1);
+        let mut recommended = Settings::default();
+        self.title = None;
+        if let Some(info) = &info {
+            print_rom_info(info);
+            self.title = Some(info.title.clone());
+            recommended.tickrate = info.tickrate;
+            recommended.palette = info.palette.clone();
+            if !info.quirks.is_empty() {
+                recommended.quirks = Some(info.quirks.clone());
+            }
+        }
+        let settings = config.for_rom(&sha1, recommended);
+        self.apply_settings(&settings)?;
+
+        self.keymap = Keymap::from_layout(self.layout);
+        for (key, host_key) in info.iter().flat_map(RomInfo::hint_bindings) {
+            self.keymap.bind(key, host_key);
+        }
+        for (key, host_keys) in settings.keymap.unwrap_or_default() {
+            let binding = format!("{}={}", key, host_keys.join(","));
+            self.keymap.apply(&keymap::parse_binding(&binding)?);
+        }
+        if let Some(keymap_file) = &self.keymap_file {
+            self.keymap.load(Path::new(keymap_file), &sha1)?;
+        }
+        for binding in &self.bindings {
+            self.keymap.apply(binding);
+        }
+
+        let cheat_file = self
+            .cheat_file
+            .as_ref()
+            .map(PathBuf::from)
+            .or_else(|| {
+                let path = cheats::default_path(&self.config_dir()?, &sha1);
+                path.exists().then_some(path)
+            })
+            .filter(|_| self.movie.is_none());
+        self.cheats = match cheat_file {
+            // A new `--cheats` file is created when the cheat menu first saves to it.
+            Some(cheat_file) => match CheatList::load(&cheat_file) {
+                Err(err) if err.kind() == io::ErrorKind::NotFound => CheatList::default(),
+                cheats => cheats?,
+            },
+            None => CheatList::default(),
+        };
+        Ok(())
+    }
+
+    /// Takes every setting not given on the command line from `settings`, and the
+    /// built in default where `settings` has none either.
+    fn apply_settings(&mut self, settings: &Settings) -> Result<(), String> {
+        self.scale_factor = self.scale_arg.or(settings.scale).unwrap_or(DEFAULT_SCALE);
+        self.palette = match (self.palette_arg, &settings.palette) {
+            (Some(palette), _) => palette,
+            (None, Some(palette)) => screen::parse_palette(palette)?,
+            (None, None) => screen::DEFAULT_PALETTE,
+        };
+        self.layout = self.layout_arg.or(settings.layout).unwrap_or_default();
+        self.tickrate = self
+            .tickrate_arg
+            .or(settings.tickrate)
+            .unwrap_or(DEFAULT_TICKRATE);
+        self.volume = self
+            .volume_arg
+            .or(settings.volume)
+            .unwrap_or(DEFAULT_VOLUME);
+        // Quirk flags can only switch quirks on, so the config's quirks are always kept.
+        self.quirks = self.quirk_args.into();
+        if let Some(quirks) = &settings.quirks {
+            self.quirks = self.quirks.union(quirks.join(",").parse()?);
+        }
+        Ok(())
+    }
+}
+
+fn print_rom_info(info: &RomInfo) {
+    if info.authors.is_empty() {
+        println!("{}", info.title);
+    } else {
+        println!("{} by {}", info.title, info.authors.join(", "));
+    }
+    if !info.is_supported() {
+        println!(
+            "Made for {}, it may not run correctly here",
+            info.platforms.join(", ")
+        );
+    }
+    if !info.keys.is_empty() {
+        let keys: Vec<String> = info
+            .keys
+            .iter()
+            .map(|(name, key)| format!("{}={:X}", name, key))
+            .collect();
+        println!("Keys: {}", keys.join(" "));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process};
+
+    use super::*;
+
+    #[test]
+    fn command_line_beats_the_rom_table_which_beats_the_defaults() {
+        let dir = env::temp_dir().join(format!("chip_8-precedence-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let (rom, other_rom) = (dir.join("game.ch8"), dir.join("other.ch8"));
+        fs::write(&rom, [0x12, 0x00]).unwrap();
+        fs::write(&other_rom, [0x12, 0x02]).unwrap();
+        let config = dir.join("config.toml");
+        fs::write(
+            &config,
+            format!(
+                "tickrate = 8\nvolume = 50\n[rom.{}]\ntickrate = 20\nquirks = [\"shift\"]\n",
+                config::rom_sha1(&[0x12, 0x00])
+            ),
+        )
+        .unwrap();
+        let build = |rom: &Path, extra: &[&str]| {
+            let mut args = vec!["chip_8", "--frontend", "headless"];
+            args.extend(["--file", rom.to_str().unwrap()]);
+            args.extend(["--config", config.to_str().unwrap()]);
+            args.extend(extra);
+            let mut options = Chip8Options::parse_from(args);
+            options.build().unwrap();
+            options
+        };
+
+        let options = build(&rom, &["--tickrate", "30", "--quirk-wrap"]);
+        assert_eq!((options.tickrate, options.volume), (30, 50));
+        assert!(options.quirks.shift && options.quirks.wrap);
+        let options = build(&rom, &[]);
+        assert_eq!(options.tickrate, 20);
+        assert!(options.quirks.shift && !options.quirks.wrap);
+        let options = build(&other_rom, &[]);
+        assert_eq!((options.tickrate, options.scale_factor), (8, DEFAULT_SCALE));
+        assert!(!options.quirks.shift);
+        fs::remove_dir_all(dir).unwrap();
     }
 }
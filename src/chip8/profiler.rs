@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::disasm;
+use crate::machine::cpu_const::PC_START;
+use crate::machine::{Machine, MEMORY_SIZE};
+
+/// Hottest instructions listed in the report.
+const HOT_SPOTS: usize = 20;
+
+/// Time spent in one subroutine, in instructions.
+#[derive(Clone, Copy, Default)]
+struct Subroutine {
+    calls: u64,
+    /// Instructions executed in the subroutine itself.
+    own: u64,
+    /// Instructions executed in it and everything it called.
+    total: u64,
+}
+
+/// Counts how often each instruction runs and how long subroutines take, following
+/// 2NNN/00EE pairs. Feed it every cycle with `record`, then `write_report`.
+pub struct Profiler {
+    counts: Vec<u64>,
+    /// Entry points of the subroutines being run, innermost last.
+    stack: Vec<u16>,
+    subroutines: HashMap<u16, Subroutine>,
+    /// Instructions executed with exactly this call stack.
+    stacks: HashMap<Vec<u16>, u64>,
+    cycles: u64,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    pub fn new() -> Self {
+        Profiler {
+            counts: vec![0; MEMORY_SIZE],
+            stack: Vec::new(),
+            subroutines: HashMap::new(),
+            stacks: HashMap::new(),
+            cycles: 0,
+        }
+    }
+
+    /// Accounts for the instruction the machine just executed.
+    pub fn record(&mut self, machine: &Machine) {
+        let pc = machine.last_pc();
+        self.counts[pc] += 1;
+        self.cycles += 1;
+        match self.stacks.get_mut(&self.stack) {
+            Some(count) => *count += 1,
+            None => {
+                self.stacks.insert(self.stack.clone(), 1);
+            }
+        }
+        if let Some(&current) = self.stack.last() {
+            self.subroutines.entry(current).or_default().own += 1;
+        }
+        let mut seen = Vec::with_capacity(self.stack.len());
+        for &address in &self.stack {
+            // Recursive calls count once towards the total.
+            if !seen.contains(&address) {
+                seen.push(address);
+                self.subroutines.entry(address).or_default().total += 1;
+            }
+        }
+
+        let opcode = machine.opcode();
+        if opcode & 0xF000 == 0x2000 {
+            let address = opcode & 0x0FFF;
+            self.subroutines.entry(address).or_default().calls += 1;
+            self.stack.push(address);
+        } else if opcode == 0x00EE {
+            self.stack.pop();
+        }
+    }
+
+    /// Forgets the call stack, for when the machine is reset.
+    pub fn restart(&mut self) {
+        self.stack.clear();
+    }
+
+    /// Writes `report.txt`, `coverage.asm` and `stacks.folded` (for flamegraph.pl or
+    /// inferno) to `dir`, for a ROM of `rom_len` bytes.
+    pub fn write_report(&self, dir: &Path, memory: &[u8], rom_len: usize) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        fs::write(dir.join("report.txt"), self.summary(memory, rom_len))?;
+        fs::write(dir.join("coverage.asm"), self.annotated(memory, rom_len))?;
+        fs::write(dir.join("stacks.folded"), self.folded())
+    }
+
+    fn summary(&self, memory: &[u8], rom_len: usize) -> String {
+        let rom = PC_START..PC_START + rom_len;
+        let executed: Vec<usize> = (0..MEMORY_SIZE)
+            .filter(|&address| self.counts[address] > 0)
+            .collect();
+        let covered = executed
+            .iter()
+            .filter(|&&address| rom.contains(&address))
+            .count()
+            * 2;
+        let mut report = String::new();
+        let _ = writeln!(report, "Instructions executed: {}", self.cycles);
+        let _ = writeln!(
+            report,
+            "Distinct instructions: {}, covering {} of {} ROM bytes ({:.1}%)",
+            executed.len(),
+            covered.min(rom_len),
+            rom_len,
+            100.0 * covered.min(rom_len) as f64 / rom_len.max(1) as f64
+        );
+
+        let mut hot = executed.clone();
+        hot.sort_by_key(|&address| std::cmp::Reverse(self.counts[address]));
+        let _ = writeln!(report, "\nHot spots:");
+        for &address in hot.iter().take(HOT_SPOTS) {
+            let _ = writeln!(
+                report,
+                "{:>12} {:>5.1}%  {:03X}  {}",
+                self.counts[address],
+                100.0 * self.counts[address] as f64 / self.cycles.max(1) as f64,
+                address,
+                instruction(memory, address)
+            );
+        }
+
+        let mut subroutines: Vec<_> = self.subroutines.iter().collect();
+        subroutines
+            .sort_by_key(|(&address, subroutine)| (std::cmp::Reverse(subroutine.total), address));
+        let _ = writeln!(report, "\nSubroutines:");
+        let _ = writeln!(
+            report,
+            "{:>7} {:>10} {:>12} {:>12} {:>9}",
+            "address", "calls", "own", "total", "per call"
+        );
+        for (address, subroutine) in subroutines {
+            let _ = writeln!(
+                report,
+                "{:>7} {:>10} {:>12} {:>12} {:>9.1}",
+                format!("{:03X}", address),
+                subroutine.calls,
+                subroutine.own,
+                subroutine.total,
+                subroutine.total as f64 / subroutine.calls.max(1) as f64
+            );
+        }
+        report
+    }
+
+    /// The ROM disassembled from 0x200 with execution counts, `-` for instructions
+    /// never run; a byte only ever run as the second half of one is shown as data.
+    fn annotated(&self, memory: &[u8], rom_len: usize) -> String {
+        let end = (PC_START + rom_len).min(MEMORY_SIZE);
+        let mut listing = String::new();
+        let mut address = PC_START;
+        while address < end {
+            let count = self.counts[address];
+            if count == 0 && address + 1 < end && self.counts[address + 1] > 0 {
+                let _ = writeln!(
+                    listing,
+                    "{:>12}  {:03X}  {:02X}    DB {:02X}",
+                    "-", address, memory[address], memory[address]
+                );
+                address += 1;
+                continue;
+            }
+            let count = match count {
+                0 => "-".to_string(),
+                count => count.to_string(),
+            };
+            let _ = writeln!(
+                listing,
+                "{:>12}  {:03X}  {:02X}{:02X}  {}",
+                count,
+                address,
+                memory[address],
+                memory.get(address + 1).copied().unwrap_or(0),
+                instruction(memory, address)
+            );
+            address += 2;
+        }
+        listing
+    }
+
+    /// One line per call stack, `main;sub_2A4;sub_31C 1234`.
+    fn folded(&self) -> String {
+        let mut lines: Vec<String> = self
+            .stacks
+            .iter()
+            .map(|(stack, count)| {
+                let mut line = "main".to_string();
+                for address in stack {
+                    let _ = write!(line, ";sub_{:03X}", address);
+                }
+                format!("{} {}\n", line, count)
+            })
+            .collect();
+        lines.sort();
+        lines.concat()
+    }
+}
+
+fn instruction(memory: &[u8], address: usize) -> String {
+    if address + 1 < memory.len() {
+        disasm::disasm_chip_8_op(memory, address)
+    } else {
+        "?".to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::machine::Quirks;
+
+    /// Calls 206 twice, which calls 20A, then loops at 204.
+    const ROM: [u8; 12] = [
+        0x22, 0x06, 0x22, 0x06, 0x12, 0x04, 0x22, 0x0A, 0x00, 0xEE, 0x00, 0xEE,
+    ];
+
+    fn profile(cycles: usize) -> (Profiler, Machine) {
+        let mut machine = Machine::new(Quirks::default());
+        machine.reset(&ROM).unwrap();
+        let mut profiler = Profiler::new();
+        for _ in 0..cycles {
+            machine.cycle(&mut || 0).unwrap();
+            profiler.record(&machine);
+        }
+        (profiler, machine)
+    }
+
+    #[test]
+    fn subroutines_count_calls_own_and_total_instructions() {
+        let (profiler, _) = profile(10);
+        let calls = |address| {
+            let subroutine = profiler.subroutines[&address];
+            (subroutine.calls, subroutine.own, subroutine.total)
+        };
+        assert_eq!(calls(0x206), (2, 4, 6));
+        assert_eq!(calls(0x20A), (2, 2, 2));
+        assert_eq!(profiler.counts[0x204], 2);
+        assert_eq!(profiler.cycles, 10);
+    }
+
+    #[test]
+    fn folded_stacks_count_each_call_stack() {
+        let (profiler, _) = profile(10);
+        assert_eq!(
+            profiler.folded(),
+            "main 4\nmain;sub_206 4\nmain;sub_206;sub_20A 2\n"
+        );
+    }
+
+    #[test]
+    fn annotated_listing_shows_counts_and_unrun_code() {
+        let (profiler, machine) = profile(10);
+        let listing = profiler.annotated(&machine.memory, ROM.len() + 2);
+        let lines: Vec<&str> = listing.lines().collect();
+        assert_eq!(lines.len(), 7);
+        assert!(lines[2].starts_with("           2  204  1204"));
+        assert!(lines[6].starts_with("           -  20C  0000"));
+    }
+}
@@ -0,0 +1,150 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use super::screenshot;
+
+const SAMPLE_RATE: u32 = 44100;
+const SAMPLES_PER_FRAME: u32 = SAMPLE_RATE / 60;
+/// Half the period of the 441 Hz square wave used for the beeper.
+const HALF_PERIOD: u32 = SAMPLE_RATE / 882;
+
+enum Sink {
+    Gif {
+        encoder: gif::Encoder<BufWriter<File>>,
+        elapsed: u64,
+    },
+    Frames {
+        dir: PathBuf,
+        samples: Vec<i16>,
+    },
+}
+
+/// Captures presented frames either as an animated GIF (`*.gif`) or, for any
+/// other path, as a directory of numbered PPM frames plus a `audio.wav` beeper track.
+pub struct Recorder {
+    sink: Sink,
+    width: u32,
+    height: u32,
+    scale: u32,
+    palette: [[u8; 3]; 2],
+    frames: u64,
+}
+
+impl Recorder {
+    pub fn create(
+        path: &Path,
+        width: u32,
+        height: u32,
+        scale: u32,
+        palette: &[[u8; 3]; 2],
+    ) -> io::Result<Self> {
+        let scale = scale.max(1);
+        let is_gif = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+        let sink = if is_gif {
+            let file = BufWriter::new(File::create(path)?);
+            let mut encoder = gif::Encoder::new(
+                file,
+                (width * scale) as u16,
+                (height * scale) as u16,
+                &palette.concat(),
+            )
+            .map_err(io::Error::other)?;
+            encoder
+                .set_repeat(gif::Repeat::Infinite)
+                .map_err(io::Error::other)?;
+            Sink::Gif {
+                encoder,
+                elapsed: 0,
+            }
+        } else {
+            fs::create_dir_all(path)?;
+            Sink::Frames {
+                dir: path.to_path_buf(),
+                samples: Vec::new(),
+            }
+        };
+        Ok(Self {
+            sink,
+            width,
+            height,
+            scale,
+            palette: *palette,
+            frames: 0,
+        })
+    }
+
+    pub fn capture(&mut self, pixels: &[u8], sound: bool) -> io::Result<()> {
+        let scaled = screenshot::scale_pixels(pixels, self.width, self.height, self.scale);
+        let width = self.width * self.scale;
+        let height = self.height * self.scale;
+        self.frames += 1;
+        match &mut self.sink {
+            Sink::Gif { encoder, elapsed } => {
+                // GIF delays are in 1/100 s, so spread 60 Hz frames over 1 and 2 ticks.
+                let end = self.frames * 100 / 60;
+                let mut frame = gif::Frame {
+                    width: width as u16,
+                    height: height as u16,
+                    buffer: scaled.into(),
+                    ..gif::Frame::default()
+                };
+                frame.delay = (end - *elapsed) as u16;
+                *elapsed = end;
+                encoder.write_frame(&frame).map_err(io::Error::other)
+            }
+            Sink::Frames { dir, samples } => {
+                let path = dir.join(format!("frame_{:06}.ppm", self.frames));
+                let mut file = BufWriter::new(File::create(path)?);
+                write!(file, "P6\n{} {}\n255\n", width, height)?;
+                for &pixel in &scaled {
+                    file.write_all(&self.palette[pixel as usize])?;
+                }
+                file.flush()?;
+
+                let start = samples.len() as u32;
+                samples.extend((start..start + SAMPLES_PER_FRAME).map(|n| {
+                    if !sound {
+                        0
+                    } else if (n / HALF_PERIOD).is_multiple_of(2) {
+                        i16::MAX / 4
+                    } else {
+                        -i16::MAX / 4
+                    }
+                }));
+                Ok(())
+            }
+        }
+    }
+
+    pub fn finish(self) -> io::Result<()> {
+        match self.sink {
+            Sink::Gif { encoder, .. } => encoder.into_inner().map_err(io::Error::other)?.flush(),
+            Sink::Frames { dir, samples } => write_wav(&dir.join("audio.wav"), &samples),
+        }
+    }
+}
+
+fn write_wav(path: &Path, samples: &[i16]) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let data_len = samples.len() as u32 * 2;
+    file.write_all(b"RIFF")?;
+    file.write_all(&(36 + data_len).to_le_bytes())?;
+    file.write_all(b"WAVEfmt ")?;
+    file.write_all(&16u32.to_le_bytes())?;
+    file.write_all(&1u16.to_le_bytes())?; // PCM
+    file.write_all(&1u16.to_le_bytes())?; // mono
+    file.write_all(&SAMPLE_RATE.to_le_bytes())?;
+    file.write_all(&(SAMPLE_RATE * 2).to_le_bytes())?;
+    file.write_all(&2u16.to_le_bytes())?;
+    file.write_all(&16u16.to_le_bytes())?;
+    file.write_all(b"data")?;
+    file.write_all(&data_len.to_le_bytes())?;
+    for sample in samples {
+        file.write_all(&sample.to_le_bytes())?;
+    }
+    file.flush()
+}
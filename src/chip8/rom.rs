@@ -0,0 +1,274 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, IsTerminal, Read, Write};
+
+pub use crate::machine::MAX_ROM_SIZE;
+
+/// Extensions of the files taken for ROMs, in archives and by the launcher.
+pub static ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "chip8", "rom"];
+
+#[derive(Debug)]
+pub enum RomError {
+    NotFound(String),
+    PermissionDenied(String),
+    Empty(String),
+    /// `size` is the number of bytes read, which stops one past `MAX_ROM_SIZE`.
+    TooLarge {
+        name: String,
+        size: usize,
+    },
+    Io(String, io::Error),
+    Archive {
+        name: String,
+        message: String,
+    },
+    /// The archive holds several ROMs and none could be picked.
+    NoSelection {
+        name: String,
+        roms: Vec<String>,
+    },
+}
+
+impl fmt::Display for RomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RomError::NotFound(name) => write!(f, "ROM {} not found", name),
+            RomError::PermissionDenied(name) => {
+                write!(f, "no permission to read ROM {}", name)
+            }
+            RomError::Empty(name) => write!(f, "ROM {} is empty", name),
+            RomError::TooLarge { name, .. } => write!(
+                f,
+                "ROM {} is over {} bytes, the most that fits in CHIP-8 memory",
+                name, MAX_ROM_SIZE
+            ),
+            RomError::Io(name, err) => write!(f, "unable to read ROM {}: {}", name, err),
+            RomError::Archive { name, message } => write!(f, "{}: {}", name, message),
+            RomError::NoSelection { name, roms } => write!(
+                f,
+                "{} holds several ROMs, pick one with {}:<rom>: {}",
+                name,
+                name,
+                roms.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RomError {}
+
+/// Reads and validates a ROM from a file, from stdin when `path` is `-`, or from a zip
+/// archive given as `pack.zip` or `pack.zip:path/inside.ch8`.
+///
+/// Returns the name of the ROM that was loaded along with its contents.
+pub fn load(path: &str) -> Result<(String, Vec<u8>), RomError> {
+    load_with_stdin(path, io::stdin())
+}
+
+/// `load` with `stdin` read in place of the real stdin.
+fn load_with_stdin(path: &str, stdin: impl Read) -> Result<(String, Vec<u8>), RomError> {
+    if let Some((archive, inner)) = split_archive_path(path) {
+        return load_from_zip(archive, inner);
+    }
+
+    if path == "-" {
+        return read("<stdin>", stdin);
+    }
+    let file = File::open(path).map_err(|err| io_error(path, err))?;
+    read(path, file)
+}
+
+/// Reads and validates a ROM, calling it `name` in errors. Stops one byte past the
+/// largest ROM that fits, so an endless stream cannot exhaust memory.
+fn read(name: &str, reader: impl Read) -> Result<(String, Vec<u8>), RomError> {
+    let mut rom = Vec::new();
+    reader
+        .take(MAX_ROM_SIZE as u64 + 1)
+        .read_to_end(&mut rom)
+        .map_err(|err| io_error(name, err))?;
+    validate(name, &rom)?;
+    Ok((name.to_string(), rom))
+}
+
+fn io_error(name: &str, err: io::Error) -> RomError {
+    match err.kind() {
+        io::ErrorKind::NotFound => RomError::NotFound(name.to_string()),
+        io::ErrorKind::PermissionDenied => RomError::PermissionDenied(name.to_string()),
+        _ => RomError::Io(name.to_string(), err),
+    }
+}
+
+/// Splits `pack.zip:inner/path` into the archive and the path inside it.
+fn split_archive_path(path: &str) -> Option<(&str, Option<&str>)> {
+    let lower = path.to_ascii_lowercase();
+    if let Some(index) = lower.find(".zip:") {
+        let (archive, inner) = path.split_at(index + ".zip".len());
+        return Some((archive, Some(&inner[1..])));
+    }
+    is_archive(path).then_some((path, None))
+}
+
+/// Whether `path` names a zip archive rather than a ROM.
+pub fn is_archive(path: &str) -> bool {
+    path.to_ascii_lowercase().ends_with(".zip")
+}
+
+/// The ROMs in a zip archive, each loadable as `archive:rom`, with its contents or why
+/// it cannot be loaded.
+pub type ArchiveRoms = Vec<(String, Result<Vec<u8>, RomError>)>;
+
+/// Reads every ROM in a zip archive, opening the archive only once.
+pub fn read_archive(archive: &str) -> Result<ArchiveRoms, RomError> {
+    let mut zip = open_zip(archive)?;
+    let roms = list_roms(&zip)
+        .into_iter()
+        .map(|inner| {
+            let name = format!("{}:{}", archive, inner);
+            let rom = match zip.by_name(&inner) {
+                Ok(entry) => read(&name, entry).map(|(_, rom)| rom),
+                Err(_) => Err(RomError::NotFound(name)),
+            };
+            (inner, rom)
+        })
+        .collect();
+    Ok(roms)
+}
+
+fn open_zip(archive: &str) -> Result<zip::ZipArchive<File>, RomError> {
+    let file = File::open(archive).map_err(|err| io_error(archive, err))?;
+    zip::ZipArchive::new(file).map_err(|err| RomError::Archive {
+        name: archive.to_string(),
+        message: err.to_string(),
+    })
+}
+
+fn load_from_zip(archive: &str, inner: Option<&str>) -> Result<(String, Vec<u8>), RomError> {
+    let archive_error = |message: String| RomError::Archive {
+        name: archive.to_string(),
+        message,
+    };
+    let mut zip = open_zip(archive)?;
+
+    let inner = match inner {
+        Some(inner) => inner.to_string(),
+        None => {
+            let roms = list_roms(&zip);
+            match roms.len() {
+                0 => return Err(archive_error("no ROMs in archive".to_string())),
+                1 => roms[0].clone(),
+                _ => pick_rom(archive, roms)?,
+            }
+        }
+    };
+    let name = format!("{}:{}", archive, inner);
+    let entry = zip
+        .by_name(&inner)
+        .map_err(|_| RomError::NotFound(name.clone()))?;
+    read(&name, entry)
+}
+
+/// Files in the archive with a ROM extension, or every file if none has one.
+fn list_roms(zip: &zip::ZipArchive<File>) -> Vec<String> {
+    let files: Vec<String> = zip
+        .file_names()
+        .filter(|name| !name.ends_with('/'))
+        .map(str::to_string)
+        .collect();
+    let mut roms: Vec<String> = files
+        .iter()
+        .filter(|name| {
+            name.rsplit_once('.')
+                .is_some_and(|(_, ext)| ROM_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        })
+        .cloned()
+        .collect();
+    if roms.is_empty() {
+        roms = files;
+    }
+    roms.sort();
+    roms
+}
+
+/// Asks on the terminal which of the ROMs in the archive to run.
+fn pick_rom(archive: &str, roms: Vec<String>) -> Result<String, RomError> {
+    let no_selection = |roms| RomError::NoSelection {
+        name: archive.to_string(),
+        roms,
+    };
+    if !io::stdin().is_terminal() {
+        return Err(no_selection(roms));
+    }
+    for (number, rom) in roms.iter().enumerate() {
+        eprintln!("{:3}) {}", number + 1, rom);
+    }
+    eprint!("ROM to run [1-{}]: ", roms.len());
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return Err(no_selection(roms));
+    }
+    match answer.trim().parse::<usize>() {
+        Ok(number) if (1..=roms.len()).contains(&number) => Ok(roms[number - 1].clone()),
+        _ => Err(no_selection(roms)),
+    }
+}
+
+pub fn validate(name: &str, rom: &[u8]) -> Result<(), RomError> {
+    if rom.is_empty() {
+        return Err(RomError::Empty(name.to_string()));
+    }
+    if rom.len() > MAX_ROM_SIZE {
+        return Err(RomError::TooLarge {
+            name: name.to_string(),
+            size: rom.len(),
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn validate_rejects_empty_and_oversize_roms() {
+        assert!(matches!(validate("a", &[]), Err(RomError::Empty(name)) if name == "a"));
+        let rom = vec![0; MAX_ROM_SIZE + 1];
+        assert!(matches!(
+            validate("b", &rom),
+            Err(RomError::TooLarge { size, .. }) if size == MAX_ROM_SIZE + 1
+        ));
+        assert!(validate("c", &rom[..MAX_ROM_SIZE]).is_ok());
+    }
+
+    #[test]
+    fn load_reads_files_and_reports_missing_ones() {
+        let path = env::temp_dir().join(format!("chip_8-rom-{}.ch8", std::process::id()));
+        let path = path.to_string_lossy().into_owned();
+        assert!(matches!(load(&path), Err(RomError::NotFound(name)) if name == path));
+        fs::write(&path, [0x12, 0x00]).unwrap();
+        let loaded = load(&path);
+        fs::write(&path, []).unwrap();
+        let empty = load(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.unwrap(), (path.clone(), vec![0x12, 0x00]));
+        assert!(matches!(empty, Err(RomError::Empty(_))));
+    }
+
+    #[test]
+    fn dash_loads_stdin() {
+        let (name, rom) = load_with_stdin("-", &[0x00, 0xE0][..]).unwrap();
+        assert_eq!((name.as_str(), rom), ("<stdin>", vec![0x00, 0xE0]));
+        let err = load_with_stdin("-", &[][..]).unwrap_err();
+        assert_eq!(err.to_string(), "ROM <stdin> is empty");
+    }
+
+    #[test]
+    fn endless_input_stops_past_the_largest_rom() {
+        let err = load_with_stdin("-", io::repeat(0)).unwrap_err();
+        assert!(matches!(err, RomError::TooLarge { size, .. } if size == MAX_ROM_SIZE + 1));
+    }
+}
@@ -0,0 +1,281 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// Platforms from the database this interpreter runs as intended, with the quirks
+/// each has unless a ROM's `quirkyPlatforms` says otherwise.
+static SUPPORTED_PLATFORMS: [(&str, &[&str]); 3] = [
+    ("originalChip8", &["vblank", "logic"]),
+    ("hybridVIP", &["vblank", "logic"]),
+    ("modernChip8", &[]),
+];
+
+/// The quirks the database describes ROMs with, all of which `Quirks` has.
+static DATABASE_QUIRKS: [&str; 7] = [
+    "shift",
+    "memoryIncrementByX",
+    "memoryLeaveIUnchanged",
+    "wrap",
+    "jump",
+    "vblank",
+    "logic",
+];
+
+/// Host keys bound to the database's key hints, on top of the regular keymap.
+static HINT_KEYS: [(&str, &[&str]); 6] = [
+    ("up", &["up", "pad dpup"]),
+    ("down", &["down", "pad dpdown"]),
+    ("left", &["left", "pad dpleft"]),
+    ("right", &["right", "pad dpright"]),
+    ("a", &["space", "pad a"]),
+    ("b", &["left shift", "pad b"]),
+];
+
+#[derive(Deserialize)]
+struct Program {
+    title: String,
+    #[serde(default)]
+    authors: Vec<String>,
+    #[serde(default)]
+    roms: HashMap<String, RomEntry>,
+}
+
+#[derive(Deserialize)]
+struct RomEntry {
+    #[serde(default)]
+    platforms: Vec<String>,
+    tickrate: Option<u32>,
+    colors: Option<Colors>,
+    #[serde(default)]
+    keys: HashMap<String, u8>,
+    /// Quirks the ROM needs set differently from the defaults of each platform.
+    #[serde(default, rename = "quirkyPlatforms")]
+    quirky_platforms: HashMap<String, HashMap<String, bool>>,
+}
+
+#[derive(Deserialize)]
+struct Colors {
+    #[serde(default)]
+    pixels: Vec<String>,
+}
+
+/// What the database knows about one ROM.
+pub struct RomInfo {
+    pub title: String,
+    pub authors: Vec<String>,
+    pub platforms: Vec<String>,
+    pub tickrate: Option<u32>,
+    /// `RRGGBB,RRGGBB`, in the config file palette syntax.
+    pub palette: Option<String>,
+    /// Semantic key names (`up`, `a`, ...) and the CHIP-8 key the game uses for them.
+    pub keys: Vec<(String, u8)>,
+    /// Quirks to switch on for the first supported platform, in the config file syntax.
+    pub quirks: Vec<String>,
+}
+
+impl RomInfo {
+    pub fn is_supported(&self) -> bool {
+        self.platforms.is_empty()
+            || self
+                .platforms
+                .iter()
+                .any(|platform| platform_quirks(platform).is_some())
+    }
+
+    /// Host keys to bind for the game's key hints, e.g. the arrow keys for `up`/`down`.
+    pub fn hint_bindings(&self) -> Vec<(u8, &'static str)> {
+        let mut bindings = Vec::new();
+        for (name, key) in &self.keys {
+            if let Some((_, host_keys)) = HINT_KEYS.iter().find(|(hint, _)| hint == name) {
+                bindings.extend(host_keys.iter().map(|&host_key| (*key, host_key)));
+            }
+        }
+        bindings
+    }
+}
+
+/// A ROM database in the format of the community chip-8-database project:
+/// `sha1-hashes.json` maps ROM hashes to indexes into `programs.json`.
+pub struct RomDatabase {
+    hashes: HashMap<String, usize>,
+    programs: Vec<Program>,
+}
+
+impl RomDatabase {
+    /// The copy of the database in `database/`, compiled into the binary; refresh it
+    /// with `database/update.sh`.
+    pub fn bundled() -> Self {
+        Self::parse(
+            include_str!("../../database/sha1-hashes.json"),
+            include_str!("../../database/programs.json"),
+        )
+        .expect("bundled ROM database is valid")
+    }
+
+    /// Loads `sha1-hashes.json` and `programs.json` from a directory.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        let hashes = fs::read_to_string(dir.join("sha1-hashes.json"))?;
+        let programs = fs::read_to_string(dir.join("programs.json"))?;
+        Self::parse(&hashes, &programs).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}: {}", dir.display(), err),
+            )
+        })
+    }
+
+    fn parse(hashes: &str, programs: &str) -> serde_json::Result<Self> {
+        Ok(Self {
+            hashes: serde_json::from_str(hashes)?,
+            programs: serde_json::from_str(programs)?,
+        })
+    }
+
+    pub fn lookup(&self, sha1: &str) -> Option<RomInfo> {
+        let sha1 = sha1.to_lowercase();
+        let program = self.programs.get(*self.hashes.get(&sha1)?)?;
+        let rom = program.roms.get(&sha1)?;
+        let palette = rom
+            .colors
+            .as_ref()
+            .filter(|colors| colors.pixels.len() >= 2)
+            .map(|colors| {
+                colors.pixels[..2]
+                    .iter()
+                    .map(|color| color.trim_start_matches('#'))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            });
+        let mut keys: Vec<(String, u8)> = rom
+            .keys
+            .iter()
+            .map(|(name, &key)| (name.clone(), key))
+            .collect();
+        keys.sort_by_key(|&(_, key)| key);
+        let quirks = rom
+            .platforms
+            .iter()
+            .find_map(|platform| Some((platform, platform_quirks(platform)?)))
+            .map(|(platform, defaults)| {
+                let overrides = rom.quirky_platforms.get(platform);
+                DATABASE_QUIRKS
+                    .iter()
+                    .filter(
+                        |&&name| match overrides.and_then(|quirks| quirks.get(name)) {
+                            Some(&enabled) => enabled,
+                            None => defaults.contains(&name),
+                        },
+                    )
+                    .map(|name| kebab_case(name))
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Some(RomInfo {
+            title: program.title.clone(),
+            authors: program.authors.clone(),
+            platforms: rom.platforms.clone(),
+            tickrate: rom.tickrate,
+            palette,
+            keys,
+            quirks,
+        })
+    }
+}
+
+/// The quirks a supported platform has by default.
+fn platform_quirks(platform: &str) -> Option<&'static [&'static str]> {
+    SUPPORTED_PLATFORMS
+        .iter()
+        .find(|(supported, _)| *supported == platform)
+        .map(|(_, quirks)| *quirks)
+}
+
+/// `memoryIncrementByX` to `memory-increment-by-x`, the database's quirk names to ours.
+fn kebab_case(name: &str) -> String {
+    let mut kebab = String::new();
+    for c in name.chars() {
+        if c.is_ascii_uppercase() {
+            kebab.push('-');
+        }
+        kebab.push(c.to_ascii_lowercase());
+    }
+    kebab
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::machine::Quirks;
+
+    /// A database in the upstream format with every quirk the database has.
+    fn fixture() -> RomDatabase {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("src/chip8/testdata/database");
+        RomDatabase::load(&dir).unwrap()
+    }
+
+    #[test]
+    fn lookup_applies_quirky_platforms_over_the_platform_defaults() {
+        let database = fixture();
+        let info = database
+            .lookup("0123456789ABCDEF0123456789ABCDEF01234567")
+            .unwrap();
+        assert_eq!(info.title, "Quirk Test");
+        assert_eq!(info.tickrate, Some(15));
+        assert_eq!(info.quirks, ["shift", "memory-increment-by-x", "logic"]);
+        let quirks: Quirks = info.quirks.join(",").parse().unwrap();
+        assert!(quirks.shift && quirks.memory_increment_by_x && quirks.logic);
+        assert!(!quirks.vblank && !quirks.key_wait_press);
+    }
+
+    #[test]
+    fn every_database_quirk_maps_onto_quirks() {
+        let info = fixture()
+            .lookup("89abcdef0123456789abcdef0123456789abcdef")
+            .unwrap();
+        assert_eq!(
+            info.quirks,
+            [
+                "shift",
+                "memory-increment-by-x",
+                "memory-leave-i-unchanged",
+                "wrap",
+                "jump",
+                "vblank",
+                "logic"
+            ]
+        );
+        assert_eq!(
+            info.quirks.join(",").parse::<Quirks>().unwrap().bits(),
+            0xFE
+        );
+    }
+
+    #[test]
+    fn every_bundled_hash_finds_its_rom() {
+        let database = RomDatabase::bundled();
+        for sha1 in database.hashes.keys() {
+            assert!(
+                database.lookup(sha1).is_some(),
+                "{} is not in programs.json",
+                sha1
+            );
+        }
+    }
+
+    #[test]
+    fn unsupported_platforms_get_no_quirks() {
+        let database = fixture();
+        let info = database
+            .lookup("fedcba9876543210fedcba9876543210fedcba98")
+            .unwrap();
+        assert!(!info.is_supported());
+        assert!(info.quirks.is_empty());
+        assert!(database.lookup(&"f".repeat(40)).is_none());
+    }
+}
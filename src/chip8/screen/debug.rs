@@ -0,0 +1,149 @@
+use std::ops::Range;
+
+use crate::chip8::sprites::{SpriteLayout, SpriteSheet};
+use crate::disasm;
+use crate::machine::{cpu_const, Machine, MEMORY_SIZE};
+
+/// Rows of 16 bytes shown around PC in the memory dump.
+const PC_ROWS: usize = 12;
+/// Rows shown from I on.
+const I_ROWS: usize = 4;
+/// Bytes drawn as sprites from the row of I.
+const SPRITE_BYTES: usize = 256;
+/// Instructions disassembled before and after PC.
+const BEFORE_PC: usize = 6;
+const AFTER_PC: usize = 12;
+
+/// How a byte of the memory dump is highlighted.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Highlight {
+    None,
+    Pc,
+    Index,
+    Stack,
+}
+
+/// A labelled run of 16 byte rows from the memory dump.
+pub struct MemorySection {
+    pub label: &'static str,
+    /// Start addresses of the rows, multiples of 16.
+    pub rows: Range<usize>,
+}
+
+/// The machine as the debug window shows it, taken once a frame.
+pub struct DebugView {
+    pub memory: Vec<u8>,
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub pc: usize,
+    pub sp: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub keys: u16,
+    pub sections: Vec<MemorySection>,
+    /// Address and text of the instructions around PC.
+    pub disassembly: Vec<(usize, String)>,
+    /// Memory from the row of I drawn as sprites, the last DXYN sprite highlighted.
+    pub sprites: SpriteSheet,
+    pub sprites_start: usize,
+}
+
+impl DebugView {
+    pub fn new(machine: &Machine, layout: SpriteLayout) -> Self {
+        let row = |address: usize| address / 16 * 16;
+        let pc_start = row(machine.pc).saturating_sub(PC_ROWS / 2 * 16);
+        let i_start = row(machine.i as usize % MEMORY_SIZE);
+        let stack_start = row(cpu_const::STACK_POINT_START);
+        let stack_end = row(machine.sp.max(cpu_const::STACK_POINT_START)) + 16;
+        let sections = vec![
+            MemorySection {
+                label: "PC",
+                rows: pc_start..(pc_start + PC_ROWS * 16).min(MEMORY_SIZE),
+            },
+            MemorySection {
+                label: "I",
+                rows: i_start..(i_start + I_ROWS * 16).min(MEMORY_SIZE),
+            },
+            MemorySection {
+                label: "STACK",
+                rows: stack_start..stack_end.min(MEMORY_SIZE),
+            },
+        ];
+
+        let first = machine.pc.saturating_sub(BEFORE_PC * 2);
+        let disassembly = (first..MEMORY_SIZE - 1)
+            .step_by(2)
+            .take(BEFORE_PC + AFTER_PC)
+            .map(|address| (address, disasm::disasm_chip_8_op(&machine.memory, address)))
+            .collect();
+
+        let sprites = SpriteSheet::new(
+            &machine.memory,
+            i_start..i_start + SPRITE_BYTES,
+            layout,
+            machine.last_sprite(),
+        );
+
+        DebugView {
+            memory: machine.memory.to_vec(),
+            registers: machine.registers,
+            i: machine.i,
+            pc: machine.pc,
+            sp: machine.sp,
+            delay_timer: machine.delay_timer,
+            sound_timer: machine.sound_timer,
+            keys: machine.keys(),
+            sections,
+            disassembly,
+            sprites,
+            sprites_start: i_start,
+        }
+    }
+
+    /// Highlight for the byte at `address`: the instruction at PC, the sprite row at I
+    /// and the used part of the stack.
+    pub fn highlight(&self, address: usize) -> Highlight {
+        if (self.pc..self.pc + 2).contains(&address) {
+            Highlight::Pc
+        } else if address == self.i as usize {
+            Highlight::Index
+        } else if (cpu_const::STACK_POINT_START..self.sp).contains(&address) {
+            Highlight::Stack
+        } else {
+            Highlight::None
+        }
+    }
+
+    /// Text lines for the registers, timers and keypad.
+    pub fn register_lines(&self) -> Vec<String> {
+        let mut lines: Vec<String> = self
+            .registers
+            .chunks(4)
+            .enumerate()
+            .map(|(row, registers)| {
+                registers
+                    .iter()
+                    .enumerate()
+                    .map(|(n, value)| format!("V{:X}={:02X}", row * 4 + n, value))
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        lines.push(format!(
+            "I={:03X} PC={:03X} SP={:03X}",
+            self.i, self.pc, self.sp
+        ));
+        lines.push(format!(
+            "DT={:02X} ST={:02X}",
+            self.delay_timer, self.sound_timer
+        ));
+        let keys: String = (0..16)
+            .map(|key| match self.keys & 1 << key {
+                0 => '.',
+                _ => std::char::from_digit(key, 16).unwrap_or('?'),
+            })
+            .collect();
+        lines.push(format!("KEYS {}", keys));
+        lines
+    }
+}
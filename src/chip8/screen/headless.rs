@@ -0,0 +1,18 @@
+use super::{Backend, HostEvent};
+
+/// Shows nothing and reads no input, for batch runs and movie playback.
+pub struct HeadlessBackend;
+
+impl Backend for HeadlessBackend {
+    fn present(&mut self, _pixels: &[u8], _width: u32, _height: u32) {}
+
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        Vec::new()
+    }
+
+    fn held_keys(&mut self) -> Vec<String> {
+        Vec::new()
+    }
+
+    fn set_status(&mut self, _status: &str) {}
+}
@@ -1,153 +1,351 @@
 use std::collections::HashSet;
+use std::io;
+use std::path::Path;
 
-use sdl2::{
-    keyboard::Keycode, pixels::Color, render::Canvas,
-    video::Window, EventPump,
-};
+use super::keymap::{Keymap, KEYPAD_ORDER};
+use super::options::Frontend;
+use super::recorder::Recorder;
+use super::screenshot;
+use crate::machine;
+
+pub mod debug;
+pub mod font;
+pub mod headless;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+pub mod tty;
+
+/// Background and foreground colors, indexed by pixel value.
+pub type Palette = [[u8; 3]; 2];
+
+pub const WIDTH: u32 = machine::WIDTH as u32;
+pub const HEIGHT: u32 = machine::HEIGHT as u32;
+
+pub static DEFAULT_PALETTE: Palette = [[0, 0, 0], [255, 255, 255]];
+
+/// Parses `RRGGBB,RRGGBB` (background, foreground), e.g. `000000,ffffff`.
+pub fn parse_palette(value: &str) -> Result<Palette, String> {
+    let colors: Vec<&str> = value.split(',').map(str::trim).collect();
+    let mut palette = DEFAULT_PALETTE;
+    if colors.len() != palette.len() {
+        return Err(format!("expected BACKGROUND,FOREGROUND, got `{}`", value));
+    }
+    for (color, hex) in palette.iter_mut().zip(colors) {
+        let rgb = u32::from_str_radix(hex.trim_start_matches('#'), 16)
+            .ok()
+            .filter(|_| hex.trim_start_matches('#').len() == 6)
+            .ok_or_else(|| format!("`{}` is not an RRGGBB color", hex))?;
+        *color = [(rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8];
+    }
+    Ok(palette)
+}
+
+pub enum Hotkey {
+    Screenshot,
+    Record,
+    Pause,
+    FrameAdvance,
+    Slower,
+    Faster,
+    Launcher,
+    Reset,
+    Cheats,
+    Debug,
+    /// A host key pressed while a menu is open.
+    MenuInput(String),
+}
+
+pub enum HostEvent {
+    Quit,
+    KeyDown(String),
+    /// The debug window was closed by the user.
+    DebugClosed,
+}
+
+/// A place to show the framebuffer and read the host keyboard from.
+///
+/// Host keys are named with lowercase SDL key names (`q`, `escape`, `f12`, `keypad 8`).
+pub trait Backend {
+    fn present(&mut self, pixels: &[u8], width: u32, height: u32);
+    /// Drains the events that arrived since the last poll.
+    fn poll_events(&mut self) -> Vec<HostEvent>;
+    fn held_keys(&mut self) -> Vec<String>;
+    /// Shows a short message to the user, or hides it when `status` is empty.
+    fn set_status(&mut self, status: &str);
+    /// Tells the user that something was done, like a screenshot saved.
+    fn notify(&mut self, message: &str) {
+        println!("{}", message);
+    }
+    /// Tells the user that something failed.
+    fn warn(&mut self, message: &str) {
+        eprintln!("{}", message);
+    }
+    /// Starts or stops the beeper.
+    fn set_sound(&mut self, _on: bool) {}
+    /// Names the running game in the window title, where there is one.
+    fn set_title(&mut self, _title: &str) {}
+    /// Draws with different colors from the next `present` on.
+    fn set_palette(&mut self, _palette: Palette) {}
+    /// Shows a list to pick from in place of the framebuffer.
+    fn show_menu(&mut self, _title: &str, _items: &[String], _selected: usize) {}
+    /// Shows the machine in a debug window next to the game, or closes it for `None`.
+    fn show_debug(&mut self, _view: Option<&debug::DebugView>) {}
+}
 
 pub struct Screen {
-    canvas: Canvas<Window>,
-    event_pump: EventPump,
+    backend: Box<dyn Backend>,
     scale_factor: u32,
     width: u32,
     height: u32,
     pixel_buffer: Vec<u8>,
+    palette: Palette,
+    hotkeys: Vec<Hotkey>,
+    keymap: Keymap,
+    /// Position in `KEYPAD_ORDER` of the key being rebound, if the binding screen is open.
+    binding: Option<usize>,
+    menu_open: bool,
+    /// Pixels shown inverted over the display, or empty for none.
+    overlay: Vec<u8>,
 }
 
 impl Screen {
-    pub fn new(scale_factor: u32) -> Self {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
-
-        let width = 64;
-        let height = 32;
-
-        let window = video_subsystem
-            .window("CHIP 8", width * scale_factor, height * scale_factor)
-            .position_centered()
-            .build()
-            .unwrap();
+    pub fn new(
+        scale_factor: u32,
+        frontend: Frontend,
+        keymap: Keymap,
+        palette: Palette,
+        volume: u8,
+    ) -> io::Result<Self> {
+        let backend: Box<dyn Backend> = match frontend {
+            #[cfg(feature = "sdl")]
+            Frontend::Sdl => Box::new(sdl::SdlBackend::new(
+                WIDTH,
+                HEIGHT,
+                scale_factor,
+                palette,
+                volume,
+            )),
+            Frontend::Tty => Box::new(tty::TtyBackend::new(palette)?),
+            Frontend::Headless => Box::new(headless::HeadlessBackend),
+        };
+        #[cfg(not(feature = "sdl"))]
+        let _ = volume;
 
-        let canvas = window.into_canvas().build().unwrap();
+        Ok(Self::with_backend(backend, scale_factor, keymap, palette))
+    }
 
-        let event_pump = sdl_context.event_pump().unwrap();
-        let pixel_buffer = vec![0; (width * height) as usize];
+    /// Builds a screen on top of a backend supplied by the caller.
+    pub fn with_backend(
+        backend: Box<dyn Backend>,
+        scale_factor: u32,
+        keymap: Keymap,
+        palette: Palette,
+    ) -> Self {
+        let pixel_buffer = vec![0; (WIDTH * HEIGHT) as usize];
 
         Self {
-            canvas,
-            event_pump,
+            backend,
             scale_factor,
-            width,
-            height,
+            width: WIDTH,
+            height: HEIGHT,
             pixel_buffer,
+            palette,
+            hotkeys: Vec::new(),
+            keymap,
+            binding: None,
+            menu_open: false,
+            overlay: Vec::new(),
         }
     }
 
     pub fn start_screen(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
-        self.canvas.present();
+        self.update_canvas();
     }
-    pub fn clear(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
-        self.canvas.present();
-        self.pixel_buffer.fill(0);
+
+    /// Shows a new framebuffer, one byte per pixel.
+    pub fn update(&mut self, pixels: &[u8]) {
+        self.pixel_buffer.copy_from_slice(pixels);
+        self.update_canvas();
     }
 
     pub fn get_key_state(&mut self) -> (HashSet<u8>, bool) {
-        let mut keys = HashSet::new();
         let mut run = true;
 
-        for event in self.event_pump.poll_iter() {
+        for event in self.backend.poll_events() {
             match event {
-                sdl2::event::Event::Quit { .. } => run = false,
-                sdl2::event::Event::KeyDown {
-                    keycode: Some(key), ..
-                } => match key {
-                    Keycode::Escape => run = false,
+                HostEvent::Quit => run = false,
+                HostEvent::DebugClosed => self.hotkeys.push(Hotkey::Debug),
+                HostEvent::KeyDown(key) if self.menu_open => {
+                    self.hotkeys.push(Hotkey::MenuInput(key))
+                }
+                HostEvent::KeyDown(key) if self.binding.is_some() => self.bind_key(&key),
+                HostEvent::KeyDown(key) => match key.as_str() {
+                    "escape" => run = false,
+                    "f2" => self.start_binding(),
+                    "f3" => self.hotkeys.push(Hotkey::Launcher),
+                    "f4" => self.hotkeys.push(Hotkey::Reset),
+                    "f5" => self.hotkeys.push(Hotkey::Pause),
+                    "f6" => self.hotkeys.push(Hotkey::FrameAdvance),
+                    "f7" => self.hotkeys.push(Hotkey::Slower),
+                    "f8" => self.hotkeys.push(Hotkey::Faster),
+                    "f9" => self.hotkeys.push(Hotkey::Cheats),
+                    "f10" => self.hotkeys.push(Hotkey::Record),
+                    "f11" => self.hotkeys.push(Hotkey::Debug),
+                    "f12" => self.hotkeys.push(Hotkey::Screenshot),
                     _ => continue,
                 },
-                _ => continue,
             }
         }
 
-        let pushed_keys: Vec<Keycode> = self
-            .event_pump
-            .keyboard_state()
-            .pressed_scancodes()
-            .filter_map(Keycode::from_scancode)
-            .collect();
-
-        for key in pushed_keys {
-            match key {
-                Keycode::Num1 => keys.insert(0x1),
-                Keycode::Num2 => keys.insert(0x2),
-                Keycode::Num3 => keys.insert(0x3),
-                Keycode::Num4 => keys.insert(0xC),
-                Keycode::Q => keys.insert(0x4),
-                Keycode::W => keys.insert(0x5),
-                Keycode::E => keys.insert(0x6),
-                Keycode::R => keys.insert(0xD),
-                Keycode::A => keys.insert(0x7),
-                Keycode::S => keys.insert(0x8),
-                Keycode::D => keys.insert(0x9),
-                Keycode::F => keys.insert(0xE),
-                Keycode::Z => keys.insert(0xA),
-                Keycode::X => keys.insert(0x0),
-                Keycode::C => keys.insert(0xB),
-                Keycode::V => keys.insert(0xF),
-                _ => false,
-            };
+        if self.binding.is_some() || self.menu_open {
+            return (HashSet::new(), run);
         }
+        let keys = self
+            .backend
+            .held_keys()
+            .iter()
+            .filter_map(|key| self.keymap.get(key))
+            .collect();
 
         (keys, run)
     }
 
-    pub fn draw(&mut self, x_start: u8, y_start: u8, sprite: &[u8]) -> bool {
-        let mut collision = false;
+    pub fn show_menu(&mut self, title: &str, items: &[String], selected: usize) {
+        self.menu_open = true;
+        self.backend.show_menu(title, items, selected);
+    }
 
-        for (y_offset, byte) in sprite.iter().enumerate() {
-            for x_offset in 0..8 {
-                if (byte & (0x80 >> x_offset)) != 0 {
-                    let x = (x_start.wrapping_add(x_offset as u8) % self.width as u8) as usize;
-                    let y = (y_start.wrapping_add(y_offset as u8) % self.height as u8) as usize;
-                    let index = x + y * self.width as usize;
+    /// Closes the menu and shows the framebuffer again.
+    pub fn close_menu(&mut self) {
+        self.menu_open = false;
+        self.update_canvas();
+    }
 
-                    // Check for collision
-                    if self.pixel_buffer[index] == 1 {
-                        collision = true;
-                    }
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
 
-                    // XOR drawing
-                    self.pixel_buffer[index] ^= 1;
-                }
-            }
+    pub fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.backend.set_palette(palette);
+        self.update_canvas();
+    }
+
+    /// Replaces the keymap, e.g. with the one of a ROM started from the launcher.
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    pub fn show_debug(&mut self, view: Option<&debug::DebugView>) {
+        self.backend.show_debug(view);
+    }
+
+    pub fn set_status(&mut self, status: &str) {
+        self.backend.set_status(status);
+    }
+
+    pub fn notify(&mut self, message: &str) {
+        self.backend.notify(message);
+    }
+
+    pub fn warn(&mut self, message: &str) {
+        self.backend.warn(message);
+    }
+
+    pub fn set_title(&mut self, title: &str) {
+        self.backend.set_title(title);
+    }
+
+    pub fn set_sound(&mut self, on: bool) {
+        self.backend.set_sound(on);
+    }
+
+    fn start_binding(&mut self) {
+        self.binding = Some(0);
+        self.prompt_binding();
+    }
+
+    fn bind_key(&mut self, host_key: &str) {
+        let Some(index) = self.binding else {
+            return;
+        };
+        if host_key == "escape" {
+            self.binding = None;
+            self.backend.set_status("");
+            return;
+        }
+        let key = KEYPAD_ORDER[index];
+        self.keymap.unbind(key);
+        self.keymap.bind(key, host_key);
+        if index + 1 < KEYPAD_ORDER.len() {
+            self.binding = Some(index + 1);
+            self.prompt_binding();
+        } else {
+            self.binding = None;
+            self.backend.set_status("");
+            self.backend.notify(&format!(
+                "New keymap, save it to a file for --keymap:\n{}",
+                self.keymap.to_config().trim_end()
+            ));
         }
+    }
 
+    fn prompt_binding(&mut self) {
+        if let Some(index) = self.binding {
+            self.backend.set_status(&format!(
+                "Press the key for {:X} (Esc to cancel)",
+                KEYPAD_ORDER[index]
+            ));
+        }
+    }
+
+    pub fn take_hotkeys(&mut self) -> Vec<Hotkey> {
+        std::mem::take(&mut self.hotkeys)
+    }
+
+    pub fn save_screenshot(&self, path: &Path) -> io::Result<()> {
+        screenshot::save(
+            path,
+            &self.pixel_buffer,
+            self.width,
+            self.height,
+            self.scale_factor,
+            &self.palette,
+        )
+    }
+
+    pub fn start_recording(&self, path: &Path) -> io::Result<Recorder> {
+        Recorder::create(
+            path,
+            self.width,
+            self.height,
+            self.scale_factor,
+            &self.palette,
+        )
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixel_buffer
+    }
+
+    /// Shows `overlay` inverted over the display until replaced; empty hides it.
+    pub fn set_overlay(&mut self, overlay: Vec<u8>) {
+        self.overlay = overlay;
         self.update_canvas();
-        collision
     }
 
     fn update_canvas(&mut self) {
-        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-        self.canvas.clear();
-        for (i, &pixel) in self.pixel_buffer.iter().enumerate() {
-            let x = (i % self.width as usize) as i32;
-            let y = (i / self.width as usize) as i32;
-            if pixel == 1 {
-                self.canvas.set_draw_color(Color::RGB(255, 255, 255));
-            } else {
-                self.canvas.set_draw_color(Color::RGB(0, 0, 0));
-            }
-            let _ = self.canvas.fill_rect(sdl2::rect::Rect::new(
-                x * self.scale_factor as i32,
-                y * self.scale_factor as i32,
-                self.scale_factor,
-                self.scale_factor,
-            ));
+        if self.overlay.is_empty() {
+            self.backend
+                .present(&self.pixel_buffer, self.width, self.height);
+        } else {
+            let pixels: Vec<u8> = self
+                .pixel_buffer
+                .iter()
+                .zip(&self.overlay)
+                .map(|(pixel, overlay)| pixel ^ overlay)
+                .collect();
+            self.backend.present(&pixels, self.width, self.height);
         }
-
-        self.canvas.present();
     }
 }
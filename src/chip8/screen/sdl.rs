@@ -0,0 +1,463 @@
+use std::collections::HashSet;
+
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    controller::{Axis, Button, GameController},
+    event::WindowEvent,
+    keyboard::Keycode,
+    pixels::Color,
+    render::Canvas,
+    video::Window,
+    EventPump, GameControllerSubsystem, VideoSubsystem,
+};
+
+use super::debug::{DebugView, Highlight};
+use super::font::{self, GLYPH_HEIGHT, GLYPH_WIDTH};
+use super::{Backend, HostEvent, Palette};
+use crate::chip8::sprites::SpriteSheet;
+
+/// Size of the debug window, and of its font pixels.
+const DEBUG_WIDTH: u32 = 720;
+const DEBUG_HEIGHT: u32 = 480;
+/// Size of a sprite pixel in the debug window.
+const DEBUG_SPRITE_SCALE: u32 = 3;
+const DEBUG_FONT_SIZE: u32 = 2;
+const DEBUG_TEXT: [u8; 3] = [200, 200, 200];
+const DEBUG_LABEL: [u8; 3] = [120, 120, 120];
+
+/// How far a stick or trigger has to move before it counts as a pressed key.
+const AXIS_DEADZONE: i16 = 16000;
+
+static BUTTONS: [Button; 15] = [
+    Button::A,
+    Button::B,
+    Button::X,
+    Button::Y,
+    Button::Back,
+    Button::Guide,
+    Button::Start,
+    Button::LeftStick,
+    Button::RightStick,
+    Button::LeftShoulder,
+    Button::RightShoulder,
+    Button::DPadUp,
+    Button::DPadDown,
+    Button::DPadLeft,
+    Button::DPadRight,
+];
+static AXES: [Axis; 6] = [
+    Axis::LeftX,
+    Axis::LeftY,
+    Axis::RightX,
+    Axis::RightY,
+    Axis::TriggerLeft,
+    Axis::TriggerRight,
+];
+
+/// Square wave played while the sound timer is running.
+pub struct Beeper {
+    phase: f32,
+    step: f32,
+    volume: f32,
+}
+
+impl AudioCallback for Beeper {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        for sample in out.iter_mut() {
+            *sample = if self.phase < 0.5 {
+                self.volume
+            } else {
+                -self.volume
+            };
+            self.phase = (self.phase + self.step) % 1.0;
+        }
+    }
+}
+
+pub struct SdlBackend {
+    canvas: Canvas<Window>,
+    event_pump: EventPump,
+    scale_factor: u32,
+    palette: Palette,
+    title: String,
+    beeper: Option<AudioDevice<Beeper>>,
+    controller_subsystem: GameControllerSubsystem,
+    controllers: Vec<GameController>,
+    /// Axis directions currently past the deadzone, to report them once as key downs.
+    active_axes: HashSet<String>,
+    video_subsystem: VideoSubsystem,
+    /// The memory and register viewer, while it is open.
+    debug_canvas: Option<Canvas<Window>>,
+    /// Set when the debug window could not be opened, to report it as closed.
+    debug_failed: bool,
+}
+
+impl SdlBackend {
+    pub fn new(width: u32, height: u32, scale_factor: u32, palette: Palette, volume: u8) -> Self {
+        let sdl_context = sdl2::init().unwrap();
+        let video_subsystem = sdl_context.video().unwrap();
+
+        let window = video_subsystem
+            .window("CHIP 8", width * scale_factor, height * scale_factor)
+            .position_centered()
+            .build()
+            .unwrap();
+
+        let canvas = window.into_canvas().build().unwrap();
+
+        let event_pump = sdl_context.event_pump().unwrap();
+        // Controllers plugged in at startup arrive as ControllerDeviceAdded events.
+        let controller_subsystem = sdl_context.game_controller().unwrap();
+
+        let beeper = if volume > 0 {
+            let desired = AudioSpecDesired {
+                freq: Some(44100),
+                channels: Some(1),
+                samples: None,
+            };
+            sdl_context
+                .audio()
+                .and_then(|audio| {
+                    audio.open_playback(None, &desired, |spec| Beeper {
+                        phase: 0.0,
+                        step: 440.0 / spec.freq as f32,
+                        volume: volume.min(100) as f32 / 100.0,
+                    })
+                })
+                .map_err(|err| eprintln!("Unable to open audio, running without sound: {}", err))
+                .ok()
+        } else {
+            None
+        };
+
+        Self {
+            canvas,
+            event_pump,
+            scale_factor,
+            palette,
+            title: "CHIP 8".to_string(),
+            beeper,
+            controller_subsystem,
+            controllers: Vec::new(),
+            active_axes: HashSet::new(),
+            video_subsystem,
+            debug_canvas: None,
+            debug_failed: false,
+        }
+    }
+
+    fn open_controller(&mut self, joystick_index: u32) {
+        match self.controller_subsystem.open(joystick_index) {
+            Ok(controller) => {
+                println!("Using controller {}", controller.name());
+                self.controllers.push(controller);
+            }
+            Err(err) => eprintln!("Unable to open controller {}: {}", joystick_index, err),
+        }
+    }
+}
+
+/// Draws `text` with its top left corner at (`x`, `y`), each font pixel `size` pixels wide.
+fn draw_text(canvas: &mut Canvas<Window>, text: &str, x: i32, y: i32, size: u32, color: [u8; 3]) {
+    let [r, g, b] = color;
+    canvas.set_draw_color(Color::RGB(r, g, b));
+    for (column, c) in text.chars().enumerate() {
+        let left = x + (column as u32 * (GLYPH_WIDTH + 1) * size) as i32;
+        for (row, bits) in font::glyph(c).iter().enumerate() {
+            for bit in 0..GLYPH_WIDTH {
+                if bits & (0b100 >> bit) != 0 {
+                    let _ = canvas.fill_rect(sdl2::rect::Rect::new(
+                        left + (bit * size) as i32,
+                        y + (row as u32 * size) as i32,
+                        size,
+                        size,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn highlight_color(highlight: Highlight) -> [u8; 3] {
+    match highlight {
+        Highlight::None => DEBUG_TEXT,
+        Highlight::Pc => [80, 230, 80],
+        Highlight::Index => [240, 210, 60],
+        Highlight::Stack => [80, 190, 240],
+    }
+}
+
+/// Draws the memory dump on the left of the debug window, and the registers and the
+/// disassembly around PC on the right.
+fn draw_debug(canvas: &mut Canvas<Window>, view: &DebugView, palette: &Palette) {
+    let size = DEBUG_FONT_SIZE;
+    let char_width = ((GLYPH_WIDTH + 1) * size) as i32;
+    let line_height = ((GLYPH_HEIGHT + 2) * size) as i32;
+    canvas.set_draw_color(Color::RGB(16, 16, 16));
+    canvas.clear();
+
+    let mut y = line_height / 2;
+    for section in &view.sections {
+        draw_text(canvas, section.label, char_width, y, size, DEBUG_LABEL);
+        y += line_height;
+        for row in section.rows.clone().step_by(16) {
+            draw_text(
+                canvas,
+                &format!("{:03X}:", row),
+                char_width,
+                y,
+                size,
+                DEBUG_LABEL,
+            );
+            for (column, address) in (row..row + 16).enumerate() {
+                let Some(byte) = view.memory.get(address) else {
+                    break;
+                };
+                let color = highlight_color(view.highlight(address));
+                let x = char_width * (6 + 3 * column as i32);
+                draw_text(canvas, &format!("{:02X}", byte), x, y, size, color);
+            }
+            y += line_height;
+        }
+        y += line_height / 2;
+    }
+
+    let label = format!("SPRITES {:03X}", view.sprites_start);
+    draw_text(canvas, &label, char_width, y, size, DEBUG_LABEL);
+    y += line_height;
+    let colors = SpriteSheet::palette(palette);
+    let sheet = &view.sprites;
+    let scale = DEBUG_SPRITE_SCALE;
+    for (n, &pixel) in sheet.pixels.iter().enumerate() {
+        let [r, g, b] = colors[pixel as usize];
+        canvas.set_draw_color(Color::RGB(r, g, b));
+        let _ = canvas.fill_rect(sdl2::rect::Rect::new(
+            char_width + (n % sheet.width) as i32 * scale as i32,
+            y + (n / sheet.width) as i32 * scale as i32,
+            scale,
+            scale,
+        ));
+    }
+
+    let left = char_width * 57;
+    let mut y = line_height / 2;
+    for line in view.register_lines() {
+        draw_text(canvas, &line, left, y, size, DEBUG_TEXT);
+        y += line_height;
+    }
+    y += line_height;
+    for (address, text) in &view.disassembly {
+        let (marker, color) = match *address == view.pc {
+            true => (">", highlight_color(Highlight::Pc)),
+            false => (" ", DEBUG_TEXT),
+        };
+        let line = format!("{}{:03X} {}", marker, address, text);
+        draw_text(canvas, &line, left, y, size, color);
+        y += line_height;
+    }
+    canvas.present();
+}
+
+/// Names a controller button like `pad a` or `pad dpup`.
+fn button_name(button: Button) -> String {
+    format!("pad {}", button.string())
+}
+
+/// Names an axis direction like `pad leftx-` or `pad righttrigger+`, if past the deadzone.
+fn axis_name(axis: Axis, value: i16) -> Option<String> {
+    if value > AXIS_DEADZONE {
+        Some(format!("pad {}+", axis.string()))
+    } else if value < -AXIS_DEADZONE {
+        Some(format!("pad {}-", axis.string()))
+    } else {
+        None
+    }
+}
+
+impl Backend for SdlBackend {
+    fn present(&mut self, pixels: &[u8], width: u32, _height: u32) {
+        self.canvas.set_draw_color(Color::RGB(0, 0, 0));
+        self.canvas.clear();
+        for (i, &pixel) in pixels.iter().enumerate() {
+            let x = (i % width as usize) as i32;
+            let y = (i / width as usize) as i32;
+            let [r, g, b] = self.palette[pixel as usize];
+            self.canvas.set_draw_color(Color::RGB(r, g, b));
+            let _ = self.canvas.fill_rect(sdl2::rect::Rect::new(
+                x * self.scale_factor as i32,
+                y * self.scale_factor as i32,
+                self.scale_factor,
+                self.scale_factor,
+            ));
+        }
+
+        self.canvas.present();
+    }
+
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        let mut events = Vec::new();
+        if std::mem::take(&mut self.debug_failed) {
+            events.push(HostEvent::DebugClosed);
+        }
+
+        let sdl_events: Vec<_> = self.event_pump.poll_iter().collect();
+        for event in sdl_events {
+            match event {
+                sdl2::event::Event::Quit { .. } => events.push(HostEvent::Quit),
+                // With the debug window open, closing either window no longer quits.
+                sdl2::event::Event::Window {
+                    window_id,
+                    win_event: WindowEvent::Close,
+                    ..
+                } => match &self.debug_canvas {
+                    Some(debug) if debug.window().id() == window_id => {
+                        self.debug_canvas = None;
+                        events.push(HostEvent::DebugClosed);
+                    }
+                    _ => events.push(HostEvent::Quit),
+                },
+                sdl2::event::Event::KeyDown {
+                    keycode: Some(key),
+                    repeat: false,
+                    ..
+                } => events.push(HostEvent::KeyDown(key.name().to_lowercase())),
+                sdl2::event::Event::ControllerDeviceAdded { which, .. } => {
+                    self.open_controller(which)
+                }
+                sdl2::event::Event::ControllerDeviceRemoved { which, .. } => self
+                    .controllers
+                    .retain(|controller| controller.instance_id() != which),
+                sdl2::event::Event::ControllerButtonDown { button, .. } => {
+                    events.push(HostEvent::KeyDown(button_name(button)))
+                }
+                sdl2::event::Event::ControllerAxisMotion { axis, value, .. } => {
+                    for direction in ["+", "-"] {
+                        self.active_axes
+                            .remove(&format!("pad {}{}", axis.string(), direction));
+                    }
+                    if let Some(name) = axis_name(axis, value) {
+                        if self.active_axes.insert(name.clone()) {
+                            events.push(HostEvent::KeyDown(name));
+                        }
+                    }
+                }
+                _ => continue,
+            }
+        }
+
+        events
+    }
+
+    fn held_keys(&mut self) -> Vec<String> {
+        let mut keys: Vec<String> = self
+            .event_pump
+            .keyboard_state()
+            .pressed_scancodes()
+            .filter_map(Keycode::from_scancode)
+            .map(|key| key.name().to_lowercase())
+            .collect();
+
+        for controller in &self.controllers {
+            for &button in &BUTTONS {
+                if controller.button(button) {
+                    keys.push(button_name(button));
+                }
+            }
+            for &axis in &AXES {
+                keys.extend(axis_name(axis, controller.axis(axis)));
+            }
+        }
+
+        keys
+    }
+
+    fn set_status(&mut self, status: &str) {
+        let title = if status.is_empty() {
+            self.title.clone()
+        } else {
+            format!("{} - {}", self.title, status)
+        };
+        let _ = self.canvas.window_mut().set_title(&title);
+    }
+
+    fn show_menu(&mut self, title: &str, items: &[String], selected: usize) {
+        let (width, height) = self.canvas.output_size().unwrap_or((640, 320));
+        let size = (self.scale_factor / 5).max(1);
+        let line_height = ((GLYPH_HEIGHT + 2) * size) as i32;
+        let columns = (width / ((GLYPH_WIDTH + 1) * size)) as usize;
+        let rows = ((height as i32 / line_height) - 2).max(1) as usize;
+        let first = selected.saturating_sub(rows - 1);
+        let [background, foreground] = self.palette;
+
+        let [r, g, b] = background;
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+        let title: String = title.chars().take(columns).collect();
+        draw_text(&mut self.canvas, &title, 0, 0, size, foreground);
+        for (row, item) in items.iter().enumerate().skip(first).take(rows) {
+            let y = (row - first + 2) as i32 * line_height;
+            let mut color = foreground;
+            if row == selected {
+                let [r, g, b] = foreground;
+                self.canvas.set_draw_color(Color::RGB(r, g, b));
+                let _ = self.canvas.fill_rect(sdl2::rect::Rect::new(
+                    0,
+                    y - size as i32,
+                    width,
+                    line_height as u32,
+                ));
+                color = background;
+            }
+            let item: String = item.chars().take(columns.saturating_sub(1)).collect();
+            draw_text(&mut self.canvas, &item, size as i32, y, size, color);
+        }
+        self.canvas.present();
+    }
+
+    fn show_debug(&mut self, view: Option<&DebugView>) {
+        let Some(view) = view else {
+            self.debug_canvas = None;
+            return;
+        };
+        if self.debug_canvas.is_none() {
+            let canvas = self
+                .video_subsystem
+                .window("CHIP 8 - Debug", DEBUG_WIDTH, DEBUG_HEIGHT)
+                .build()
+                .map_err(|err| err.to_string())
+                .and_then(|window| window.into_canvas().build().map_err(|err| err.to_string()));
+            match canvas {
+                Ok(canvas) => self.debug_canvas = Some(canvas),
+                Err(err) => {
+                    eprintln!("Unable to open the debug window: {}", err);
+                    self.debug_failed = true;
+                    return;
+                }
+            }
+        }
+        if let Some(canvas) = &mut self.debug_canvas {
+            draw_debug(canvas, view, &self.palette);
+        }
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = format!("CHIP 8 - {}", title);
+        let _ = self.canvas.window_mut().set_title(&self.title);
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+    }
+
+    fn set_sound(&mut self, on: bool) {
+        if let Some(beeper) = &self.beeper {
+            if on {
+                beeper.resume();
+            } else {
+                beeper.pause();
+            }
+        }
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::io::{self, Stdout, Write};
+use std::time::{Duration, Instant};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    queue,
+    style::{Color, Print, ResetColor, SetBackgroundColor, SetForegroundColor},
+    terminal,
+};
+
+use super::{Backend, HostEvent, Palette};
+
+/// Without key release events a key counts as held for this long after its last press or repeat.
+const KEY_HOLD: Duration = Duration::from_millis(150);
+const FRAME_INTERVAL: Duration = Duration::from_millis(16);
+
+/// Draws two pixel rows per text line with Unicode upper half blocks and reads
+/// the keypad from the terminal in raw mode.
+pub struct TtyBackend {
+    stdout: Stdout,
+    palette: Palette,
+    release_events: bool,
+    held: HashMap<String, Instant>,
+    frame: Vec<u8>,
+    width: u32,
+    height: u32,
+    status: String,
+    dirty: bool,
+    last_render: Instant,
+    /// Messages and whether each is an error, printed once the terminal is restored
+    /// since printing them now would garble the picture.
+    messages: Vec<(String, bool)>,
+}
+
+impl TtyBackend {
+    /// Takes over the terminal; fails when stdout is not one.
+    pub fn new(palette: Palette) -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let release_events = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        // Built before the terminal is set up so that `drop` restores it on failure.
+        let mut backend = Self {
+            stdout: io::stdout(),
+            palette,
+            release_events,
+            held: HashMap::new(),
+            frame: Vec::new(),
+            width: 0,
+            height: 0,
+            status: String::new(),
+            dirty: false,
+            last_render: Instant::now() - FRAME_INTERVAL,
+            messages: Vec::new(),
+        };
+        queue!(
+            backend.stdout,
+            terminal::EnterAlternateScreen,
+            cursor::Hide,
+            terminal::Clear(terminal::ClearType::All)
+        )?;
+        if release_events {
+            queue!(
+                backend.stdout,
+                PushKeyboardEnhancementFlags(KeyboardEnhancementFlags::REPORT_EVENT_TYPES)
+            )?;
+        }
+        backend.stdout.flush()?;
+        Ok(backend)
+    }
+
+    /// Shows the first line of `message` now and all of it on exit.
+    fn log(&mut self, message: &str, is_error: bool) {
+        self.set_status(message.lines().next().unwrap_or_default());
+        self.messages.push((message.to_string(), is_error));
+    }
+
+    fn render(&mut self) -> io::Result<()> {
+        queue!(self.stdout, cursor::MoveTo(0, 0))?;
+        let width = self.width as usize;
+        for row in 0..self.height.div_ceil(2) as usize {
+            let mut colors = None;
+            for x in 0..width {
+                let top = self.frame[2 * row * width + x];
+                let bottom = self
+                    .frame
+                    .get((2 * row + 1) * width + x)
+                    .copied()
+                    .unwrap_or(0);
+                if colors != Some((top, bottom)) {
+                    let ([tr, tg, tb], [br, bg, bb]) =
+                        (self.palette[top as usize], self.palette[bottom as usize]);
+                    queue!(
+                        self.stdout,
+                        SetForegroundColor(Color::Rgb {
+                            r: tr,
+                            g: tg,
+                            b: tb
+                        }),
+                        SetBackgroundColor(Color::Rgb {
+                            r: br,
+                            g: bg,
+                            b: bb
+                        })
+                    )?;
+                    colors = Some((top, bottom));
+                }
+                queue!(self.stdout, Print('▀'))?;
+            }
+            queue!(self.stdout, ResetColor, Print("\r\n"))?;
+        }
+        queue!(
+            self.stdout,
+            terminal::Clear(terminal::ClearType::UntilNewLine),
+            Print(&self.status)
+        )?;
+        self.stdout.flush()?;
+        self.dirty = false;
+        self.last_render = Instant::now();
+        Ok(())
+    }
+
+    fn render_if_due(&mut self) {
+        if self.dirty && self.last_render.elapsed() >= FRAME_INTERVAL {
+            let _ = self.render();
+        }
+    }
+}
+
+impl Drop for TtyBackend {
+    fn drop(&mut self) {
+        if self.release_events {
+            let _ = queue!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = queue!(
+            self.stdout,
+            ResetColor,
+            cursor::Show,
+            terminal::LeaveAlternateScreen
+        );
+        let _ = self.stdout.flush();
+        let _ = terminal::disable_raw_mode();
+        for (message, is_error) in &self.messages {
+            if *is_error {
+                eprintln!("{}", message);
+            } else {
+                println!("{}", message);
+            }
+        }
+    }
+}
+
+impl Backend for TtyBackend {
+    fn present(&mut self, pixels: &[u8], width: u32, height: u32) {
+        self.frame.clear();
+        self.frame.extend_from_slice(pixels);
+        self.width = width;
+        self.height = height;
+        self.dirty = true;
+        self.render_if_due();
+    }
+
+    fn poll_events(&mut self) -> Vec<HostEvent> {
+        let mut events = Vec::new();
+
+        while let Ok(true) = event::poll(Duration::ZERO) {
+            let Ok(Event::Key(KeyEvent {
+                code,
+                modifiers,
+                kind,
+                ..
+            })) = event::read()
+            else {
+                continue;
+            };
+            let Some(name) = key_name(code) else {
+                continue;
+            };
+            match kind {
+                KeyEventKind::Release => {
+                    self.held.remove(&name);
+                }
+                _ if name == "c" && modifiers.contains(KeyModifiers::CONTROL) => {
+                    events.push(HostEvent::Quit)
+                }
+                KeyEventKind::Press => {
+                    self.held.insert(name.clone(), Instant::now());
+                    events.push(HostEvent::KeyDown(name));
+                }
+                KeyEventKind::Repeat => {
+                    self.held.insert(name, Instant::now());
+                }
+            }
+        }
+
+        self.render_if_due();
+        events
+    }
+
+    fn held_keys(&mut self) -> Vec<String> {
+        if !self.release_events {
+            self.held.retain(|_, pressed| pressed.elapsed() < KEY_HOLD);
+        }
+        self.held.keys().cloned().collect()
+    }
+
+    fn set_status(&mut self, status: &str) {
+        self.status = status.to_string();
+        self.dirty = true;
+    }
+
+    fn notify(&mut self, message: &str) {
+        self.log(message, false);
+    }
+
+    fn warn(&mut self, message: &str) {
+        self.log(message, true);
+    }
+
+    fn set_palette(&mut self, palette: Palette) {
+        self.palette = palette;
+        self.dirty = true;
+    }
+
+    fn show_menu(&mut self, title: &str, items: &[String], selected: usize) {
+        let (_, rows) = terminal::size().unwrap_or((80, 24));
+        let rows = (rows as usize).saturating_sub(2).max(1);
+        let first = selected.saturating_sub(rows - 1);
+        let _ = queue!(
+            self.stdout,
+            ResetColor,
+            terminal::Clear(terminal::ClearType::All),
+            cursor::MoveTo(0, 0),
+            Print(title),
+            Print("\r\n")
+        );
+        for (row, item) in items.iter().enumerate().skip(first).take(rows) {
+            let marker = if row == selected { "> " } else { "  " };
+            let _ = queue!(self.stdout, Print("\r\n"), Print(marker), Print(item));
+        }
+        let _ = self.stdout.flush();
+        // Repaint the whole framebuffer once the menu is gone.
+        let _ = queue!(self.stdout, terminal::Clear(terminal::ClearType::All));
+        self.dirty = false;
+    }
+}
+
+/// Names keys the way SDL does, lowercased, so keymaps work with either frontend.
+fn key_name(code: KeyCode) -> Option<String> {
+    let name = match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_lowercase().to_string(),
+        KeyCode::F(n) => format!("f{}", n),
+        KeyCode::Esc => "escape".to_string(),
+        KeyCode::Enter => "return".to_string(),
+        KeyCode::Backspace => "backspace".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::Up => "up".to_string(),
+        KeyCode::Down => "down".to_string(),
+        KeyCode::Left => "left".to_string(),
+        KeyCode::Right => "right".to_string(),
+        KeyCode::PageUp => "pageup".to_string(),
+        KeyCode::PageDown => "pagedown".to_string(),
+        KeyCode::Home => "home".to_string(),
+        KeyCode::End => "end".to_string(),
+        _ => return None,
+    };
+    Some(name)
+}
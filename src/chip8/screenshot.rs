@@ -0,0 +1,165 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+pub enum ImageFormat {
+    Png,
+    Pbm,
+}
+
+impl ImageFormat {
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("pbm") => ImageFormat::Pbm,
+            _ => ImageFormat::Png,
+        }
+    }
+}
+
+/// Writes a 1-bit pixel buffer to `path`, picking PNG or PBM from the extension.
+/// Every CHIP-8 pixel becomes a `scale` x `scale` block.
+pub fn save(
+    path: &Path,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    scale: u32,
+    palette: &[[u8; 3]; 2],
+) -> io::Result<()> {
+    match ImageFormat::from_path(path) {
+        ImageFormat::Png => save_png(path, pixels, width, height, scale, palette),
+        ImageFormat::Pbm => {
+            let (scaled, width, height) = scale_image(pixels, width, height, scale);
+            let writer = BufWriter::new(File::create(path)?);
+            write_pbm(writer, &scaled, width, height, palette)
+        }
+    }
+}
+
+/// `scale_pixels` with a scale of at least 1, along with the scaled size.
+fn scale_image(pixels: &[u8], width: u32, height: u32, scale: u32) -> (Vec<u8>, u32, u32) {
+    let scale = scale.max(1);
+    let scaled = scale_pixels(pixels, width, height, scale);
+    (scaled, width * scale, height * scale)
+}
+
+pub fn scale_pixels(pixels: &[u8], width: u32, height: u32, scale: u32) -> Vec<u8> {
+    let mut scaled = Vec::with_capacity((width * height * scale * scale) as usize);
+    for row in pixels.chunks(width as usize).take(height as usize) {
+        let mut line = Vec::with_capacity((width * scale) as usize);
+        for &pixel in row {
+            line.extend(std::iter::repeat_n(pixel, scale as usize));
+        }
+        for _ in 0..scale {
+            scaled.extend_from_slice(&line);
+        }
+    }
+    scaled
+}
+
+/// Writes a PNG of pixels that index into `palette`, each one `scale` x `scale`.
+pub fn save_png(
+    path: &Path,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    scale: u32,
+    palette: &[[u8; 3]],
+) -> io::Result<()> {
+    let (scaled, width, height) = scale_image(pixels, width, height, scale);
+    write_png(
+        BufWriter::new(File::create(path)?),
+        &scaled,
+        width,
+        height,
+        palette,
+    )
+}
+
+fn write_png<W: Write>(
+    writer: W,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    palette: &[[u8; 3]],
+) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_palette(palette.concat());
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(pixels)?;
+    Ok(())
+}
+
+/// Writes a black and white PBM, black where the pixel has the darker of the two
+/// palette colors.
+fn write_pbm<W: Write>(
+    mut writer: W,
+    pixels: &[u8],
+    width: u32,
+    height: u32,
+    palette: &[[u8; 3]; 2],
+) -> io::Result<()> {
+    let brightness = |[r, g, b]: [u8; 3]| u32::from(r) + u32::from(g) + u32::from(b);
+    let black = u8::from(brightness(palette[1]) < brightness(palette[0]));
+    writeln!(writer, "P4")?;
+    writeln!(writer, "{} {}", width, height)?;
+    for row in pixels.chunks(width as usize) {
+        let mut packed = vec![0u8; row.len().div_ceil(8)];
+        for (x, &pixel) in row.iter().enumerate() {
+            if pixel == black {
+                packed[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+        writer.write_all(&packed)?;
+    }
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A lit pixel at the top left and one at the bottom right of a 3x2 image.
+    const PIXELS: [u8; 6] = [1, 0, 0, 0, 0, 1];
+    const WHITE_ON_BLACK: [[u8; 3]; 2] = [[0, 0, 0], [255, 255, 255]];
+
+    #[test]
+    fn scale_pixels_repeats_each_pixel_in_a_block() {
+        assert_eq!(
+            scale_pixels(&PIXELS, 3, 2, 2),
+            [
+                1, 1, 0, 0, 0, 0, //
+                1, 1, 0, 0, 0, 0, //
+                0, 0, 0, 0, 1, 1, //
+                0, 0, 0, 0, 1, 1,
+            ]
+        );
+    }
+
+    #[test]
+    fn png_keeps_the_pixels_and_the_palette() {
+        let mut png = Vec::new();
+        write_png(&mut png, &PIXELS, 3, 2, &WHITE_ON_BLACK).unwrap();
+        let mut reader = png::Decoder::new(io::Cursor::new(png)).read_info().unwrap();
+        let info = reader.info();
+        assert_eq!((info.width, info.height), (3, 2));
+        assert_eq!(info.palette.as_deref(), Some(&[0, 0, 0, 255, 255, 255][..]));
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        reader.next_frame(&mut pixels).unwrap();
+        assert_eq!(pixels, PIXELS);
+    }
+
+    #[test]
+    fn pbm_draws_the_darker_palette_color_black() {
+        let mut pbm = Vec::new();
+        write_pbm(&mut pbm, &PIXELS, 3, 2, &WHITE_ON_BLACK).unwrap();
+        assert_eq!(pbm, b"P4\n3 2\n\x60\xC0");
+
+        let black_on_white = [WHITE_ON_BLACK[1], WHITE_ON_BLACK[0]];
+        let mut pbm = Vec::new();
+        write_pbm(&mut pbm, &PIXELS, 3, 2, &black_on_white).unwrap();
+        assert_eq!(pbm, b"P4\n3 2\n\x80\x20");
+    }
+}
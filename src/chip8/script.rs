@@ -0,0 +1,356 @@
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::rc::Rc;
+
+use rhai::{Dynamic, Engine, FnPtr, FuncArgs, AST};
+
+use super::screen::font::{self, GLYPH_WIDTH};
+use crate::machine::{Machine, Quirks, HEIGHT, MEMORY_SIZE, WIDTH};
+
+/// What the script's functions work on. The emulator's machine is swapped in while
+/// the script runs, so `machine` is only meaningful then.
+struct Context {
+    machine: Machine,
+    frames: usize,
+    /// Keys held by the script, on top of the player's.
+    keys: u16,
+    /// Pixels drawn with `text` and `rect`, shown inverted over the display.
+    overlay: Vec<u8>,
+    overlay_changed: bool,
+    quit: bool,
+    frame_hooks: Vec<FnPtr>,
+    exec_hooks: Vec<(usize, FnPtr)>,
+    write_hooks: Vec<(usize, FnPtr)>,
+}
+
+impl Context {
+    fn set_pixel(&mut self, x: i64, y: i64) {
+        if (0..WIDTH as i64).contains(&x) && (0..HEIGHT as i64).contains(&y) {
+            self.overlay[y as usize * WIDTH + x as usize] = 1;
+            self.overlay_changed = true;
+        }
+    }
+}
+
+/// A Rhai script driving the emulator through callbacks:
+///
+/// - `on_frame(|frame| ...)` after every frame,
+/// - `on_exec(address, |address| ...)` before the instruction at `address` runs,
+/// - `on_write(address, |address, value| ...)` after an instruction writes `address`.
+///
+/// The callbacks can use `reg(n)`, `set_reg(n, value)`, `index()`, `set_index(value)`,
+/// `pc()`, `delay_timer()`, `sound_timer()`, `peek(address)`, `poke(address, value)`,
+/// `frame()`, `press(key)`, `release(key)`, `text(x, y, string)`, `rect(x, y, w, h)`,
+/// `clear()` for the overlay, and `quit()`.
+pub struct Script {
+    engine: Engine,
+    ast: AST,
+    context: Rc<RefCell<Context>>,
+}
+
+fn memory_index(address: i64) -> usize {
+    address.rem_euclid(MEMORY_SIZE as i64) as usize
+}
+
+impl Script {
+    pub fn load(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let source = fs::read_to_string(path)
+            .map_err(|err| format!("unable to read script {}: {}", path.display(), err))?;
+        Self::compile(&path.display().to_string(), &source)
+    }
+
+    /// Compiles `source`, calling it `name` in errors.
+    fn compile(name: &str, source: &str) -> Result<Self, Box<dyn Error>> {
+        let context = Rc::new(RefCell::new(Context {
+            machine: Machine::new(Quirks::default()),
+            frames: 0,
+            keys: 0,
+            overlay: vec![0; WIDTH * HEIGHT],
+            overlay_changed: false,
+            quit: false,
+            frame_hooks: Vec::new(),
+            exec_hooks: Vec::new(),
+            write_hooks: Vec::new(),
+        }));
+        let mut engine = Engine::new();
+        register_api(&mut engine, &context);
+        let ast = engine
+            .compile(source)
+            .map_err(|err| format!("{}: {}", name, err))?;
+        Ok(Script {
+            engine,
+            ast,
+            context,
+        })
+    }
+
+    /// Runs the top level of the script, which registers its callbacks.
+    pub fn start(&mut self, machine: &mut Machine) {
+        self.with_machine(machine, |script| {
+            if let Err(err) = script.engine.run_ast(&script.ast) {
+                eprintln!("script: {}", err);
+            }
+        });
+    }
+
+    pub fn before_instruction(&mut self, machine: &mut Machine) {
+        let hooks: Vec<FnPtr> = {
+            let context = self.context.borrow();
+            if context.exec_hooks.is_empty() {
+                return;
+            }
+            context
+                .exec_hooks
+                .iter()
+                .filter(|(address, _)| *address == machine.pc)
+                .map(|(_, hook)| hook.clone())
+                .collect()
+        };
+        if hooks.is_empty() {
+            return;
+        }
+        let pc = machine.pc as i64;
+        self.with_machine(machine, |script| {
+            for hook in &hooks {
+                script.call(hook, (pc,));
+            }
+        });
+    }
+
+    pub fn after_instruction(&mut self, machine: &mut Machine) {
+        let Some(written) = machine.last_write() else {
+            return;
+        };
+        let hooks: Vec<(usize, FnPtr)> = self
+            .context
+            .borrow()
+            .write_hooks
+            .iter()
+            .filter(|(address, _)| written.contains(address))
+            .cloned()
+            .collect();
+        if hooks.is_empty() {
+            return;
+        }
+        self.with_machine(machine, |script| {
+            for (address, hook) in &hooks {
+                let value = script.context.borrow().machine.memory[*address] as i64;
+                script.call(hook, (*address as i64, value));
+            }
+        });
+    }
+
+    pub fn end_frame(&mut self, machine: &mut Machine, frames: usize) {
+        let hooks = {
+            let mut context = self.context.borrow_mut();
+            context.frames = frames;
+            context.frame_hooks.clone()
+        };
+        if hooks.is_empty() {
+            return;
+        }
+        self.with_machine(machine, |script| {
+            for hook in &hooks {
+                script.call(hook, (frames as i64,));
+            }
+        });
+    }
+
+    /// Keys the script holds down.
+    pub fn keys(&self) -> u16 {
+        self.context.borrow().keys
+    }
+
+    /// The overlay, if it changed since the last call.
+    pub fn take_overlay(&mut self) -> Option<Vec<u8>> {
+        let mut context = self.context.borrow_mut();
+        std::mem::take(&mut context.overlay_changed).then(|| context.overlay.clone())
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.context.borrow().quit
+    }
+
+    fn with_machine(&mut self, machine: &mut Machine, run: impl FnOnce(&Self)) {
+        std::mem::swap(machine, &mut self.context.borrow_mut().machine);
+        run(self);
+        std::mem::swap(machine, &mut self.context.borrow_mut().machine);
+    }
+
+    fn call(&self, hook: &FnPtr, args: impl FuncArgs) {
+        if let Err(err) = hook.call::<Dynamic>(&self.engine, &self.ast, args) {
+            eprintln!("script: {}", err);
+        }
+    }
+}
+
+fn register_api(engine: &mut Engine, context: &Rc<RefCell<Context>>) {
+    let shared = context.clone();
+    engine.register_fn("on_frame", move |hook: FnPtr| {
+        shared.borrow_mut().frame_hooks.push(hook);
+    });
+    let shared = context.clone();
+    engine.register_fn("on_exec", move |address: i64, hook: FnPtr| {
+        shared
+            .borrow_mut()
+            .exec_hooks
+            .push((memory_index(address), hook));
+    });
+    let shared = context.clone();
+    engine.register_fn("on_write", move |address: i64, hook: FnPtr| {
+        shared
+            .borrow_mut()
+            .write_hooks
+            .push((memory_index(address), hook));
+    });
+
+    let shared = context.clone();
+    engine.register_fn("reg", move |register: i64| {
+        shared.borrow().machine.registers[(register & 0xF) as usize] as i64
+    });
+    let shared = context.clone();
+    engine.register_fn("set_reg", move |register: i64, value: i64| {
+        shared.borrow_mut().machine.registers[(register & 0xF) as usize] = value as u8;
+    });
+    let shared = context.clone();
+    engine.register_fn("index", move || shared.borrow().machine.i as i64);
+    let shared = context.clone();
+    engine.register_fn("set_index", move |value: i64| {
+        shared.borrow_mut().machine.i = value as u16;
+    });
+    let shared = context.clone();
+    engine.register_fn("pc", move || shared.borrow().machine.pc as i64);
+    let shared = context.clone();
+    engine.register_fn("delay_timer", move || {
+        shared.borrow().machine.delay_timer as i64
+    });
+    let shared = context.clone();
+    engine.register_fn("sound_timer", move || {
+        shared.borrow().machine.sound_timer as i64
+    });
+    let shared = context.clone();
+    engine.register_fn("peek", move |address: i64| {
+        shared.borrow().machine.memory[memory_index(address)] as i64
+    });
+    let shared = context.clone();
+    engine.register_fn("poke", move |address: i64, value: i64| {
+        shared.borrow_mut().machine.memory[memory_index(address)] = value as u8;
+    });
+    let shared = context.clone();
+    engine.register_fn("frame", move || shared.borrow().frames as i64);
+
+    let shared = context.clone();
+    engine.register_fn("press", move |key: i64| {
+        shared.borrow_mut().keys |= 1 << (key & 0xF);
+    });
+    let shared = context.clone();
+    engine.register_fn("release", move |key: i64| {
+        shared.borrow_mut().keys &= !(1 << (key & 0xF));
+    });
+
+    let shared = context.clone();
+    engine.register_fn("text", move |x: i64, y: i64, text: &str| {
+        let mut context = shared.borrow_mut();
+        for (n, c) in text.chars().enumerate() {
+            let left = x.saturating_add((n as i64).saturating_mul(GLYPH_WIDTH as i64 + 1));
+            for (row, bits) in font::glyph(c).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                        context.set_pixel(
+                            left.saturating_add(column as i64),
+                            y.saturating_add(row as i64),
+                        );
+                    }
+                }
+            }
+        }
+    });
+    let shared = context.clone();
+    engine.register_fn("rect", move |x: i64, y: i64, width: i64, height: i64| {
+        let mut context = shared.borrow_mut();
+        for row in y.max(0)..y.saturating_add(height).min(HEIGHT as i64) {
+            for column in x.max(0)..x.saturating_add(width).min(WIDTH as i64) {
+                context.set_pixel(column, row);
+            }
+        }
+    });
+    let shared = context.clone();
+    engine.register_fn("clear", move || {
+        let mut context = shared.borrow_mut();
+        context.overlay.fill(0);
+        context.overlay_changed = true;
+    });
+    let shared = context.clone();
+    engine.register_fn("quit", move || shared.borrow_mut().quit = true);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// V0 = 5, I = 0x300, stores V0 at I, then loops.
+    const ROM: [u8; 8] = [0x60, 0x05, 0xA3, 0x00, 0xF0, 0x55, 0x12, 0x06];
+
+    /// Runs frames of four instructions until the script quits or `frames` have run,
+    /// returning how many ran.
+    fn run(script: &mut Script, machine: &mut Machine, frames: usize) -> usize {
+        script.start(machine);
+        for frame in 1..=frames {
+            for _ in 0..4 {
+                script.before_instruction(machine);
+                machine.cycle(&mut || 0).unwrap();
+                script.after_instruction(machine);
+            }
+            script.end_frame(machine, frame);
+            if script.quit_requested() {
+                return frame;
+            }
+        }
+        frames
+    }
+
+    fn machine() -> Machine {
+        let mut machine = Machine::new(Quirks::default());
+        machine.reset(&ROM).unwrap();
+        machine
+    }
+
+    #[test]
+    fn hooks_see_and_change_the_machine() {
+        let mut script = Script::compile(
+            "test",
+            "on_exec(0x202, |address| poke(0x310, address - 0x200));
+             on_write(0x300, |address, value| set_reg(1, value + 1));
+             on_frame(|frame| { press(frame); if frame == 2 { quit(); } });",
+        )
+        .unwrap();
+        let mut machine = machine();
+        assert_eq!(run(&mut script, &mut machine, 5), 2);
+        assert_eq!(machine.memory[0x310], 2);
+        assert_eq!(machine.memory[0x300], 5);
+        assert_eq!(machine.registers[1], 6);
+        assert_eq!(script.keys(), 0b110);
+    }
+
+    #[test]
+    fn text_and_rect_draw_the_overlay() {
+        let mut script = Script::compile(
+            "test",
+            "text(0, 0, \"1\");
+             rect(60, 30, 10, 10);
+             text(9223372036854775807, 9223372036854775807, \"11\");
+             rect(10, 10, 9223372036854775807, 0);",
+        )
+        .unwrap();
+        let mut machine = machine();
+        run(&mut script, &mut machine, 1);
+        let overlay = script.take_overlay().unwrap();
+        let lit = |x: usize, y: usize| overlay[y * WIDTH + x] == 1;
+        assert!(lit(1, 0) && !lit(0, 0) && lit(0, 1));
+        assert!(lit(60, 30) && lit(63, 31) && !lit(59, 31));
+        assert_eq!(overlay.iter().filter(|&&pixel| pixel == 1).count(), 8 + 8);
+        assert_eq!(script.take_overlay(), None);
+    }
+}
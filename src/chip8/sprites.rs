@@ -0,0 +1,176 @@
+use std::io;
+use std::ops::Range;
+use std::path::Path;
+
+use clap::ValueEnum;
+
+use super::screen::Palette;
+use super::screenshot;
+
+/// Bytes shown in each band of the sheet; bands are stacked top to bottom.
+const BAND_BYTES: usize = 256;
+/// Height of a band, with the line between bands.
+const BAND_HEIGHT: usize = 17;
+/// Pixel values in a sheet, indexing into the palette from `SpriteSheet::palette`.
+const OFF: u8 = 0;
+const ON: u8 = 1;
+const HIGHLIGHT_OFF: u8 = 2;
+const HIGHLIGHT_ON: u8 = 3;
+const GAP: u8 = 4;
+
+/// How bytes are arranged into sprites.
+#[derive(Clone, Copy, Default, PartialEq, ValueEnum)]
+pub enum SpriteLayout {
+    /// One byte per 8 pixel row, in columns of 16 rows.
+    #[default]
+    Chip8,
+    /// SCHIP 16x16 sprites of 32 bytes, two bytes per row.
+    Schip,
+}
+
+impl SpriteLayout {
+    fn width(self) -> usize {
+        match self {
+            SpriteLayout::Chip8 => 16 * 9 - 1,
+            SpriteLayout::Schip => 8 * 17 - 1,
+        }
+    }
+
+    /// Top left pixel of the 8 pixels showing the byte at `offset` into the region.
+    fn position(self, offset: usize) -> (usize, usize) {
+        let y = offset / BAND_BYTES * BAND_HEIGHT;
+        let offset = offset % BAND_BYTES;
+        match self {
+            SpriteLayout::Chip8 => (offset / 16 * 9, y + offset % 16),
+            SpriteLayout::Schip => {
+                let byte = offset % 32;
+                (offset / 32 * 17 + byte % 2 * 8, y + byte / 2)
+            }
+        }
+    }
+}
+
+/// A region of memory drawn as sprites, to find and inspect a ROM's graphics.
+pub struct SpriteSheet {
+    pub width: usize,
+    pub height: usize,
+    /// Pixels indexing into `SpriteSheet::palette`.
+    pub pixels: Vec<u8>,
+}
+
+impl SpriteSheet {
+    /// Draws `region` of `memory`, marking the bytes in `highlight`, typically the
+    /// sprite drawn last by DXYN.
+    pub fn new(
+        memory: &[u8],
+        region: Range<usize>,
+        layout: SpriteLayout,
+        highlight: Option<Range<usize>>,
+    ) -> Self {
+        let region = region.start.min(memory.len())..region.end.min(memory.len());
+        let bands = region.len().div_ceil(BAND_BYTES).max(1);
+        let width = layout.width();
+        let height = bands * BAND_HEIGHT - 1;
+        let mut pixels = vec![GAP; width * height];
+        for (offset, address) in region.enumerate() {
+            let (x, y) = layout.position(offset);
+            let highlighted = highlight
+                .as_ref()
+                .is_some_and(|range| range.contains(&address));
+            for bit in 0..8 {
+                let on = memory[address] & (0x80 >> bit) != 0;
+                pixels[y * width + x + bit] = match (highlighted, on) {
+                    (false, false) => OFF,
+                    (false, true) => ON,
+                    (true, false) => HIGHLIGHT_OFF,
+                    (true, true) => HIGHLIGHT_ON,
+                };
+            }
+        }
+        SpriteSheet {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Colors for the pixel values: the game's background and foreground, then the
+    /// same in red for highlighted bytes, then grey between sprites.
+    pub fn palette(palette: &Palette) -> [[u8; 3]; 5] {
+        let [background, foreground] = *palette;
+        [
+            background,
+            foreground,
+            [96, 24, 16],
+            [255, 96, 64],
+            [64, 64, 64],
+        ]
+    }
+
+    pub fn save_png(&self, path: &Path, scale: u32, palette: &Palette) -> io::Result<()> {
+        screenshot::save_png(
+            path,
+            &self.pixels,
+            self.width as u32,
+            self.height as u32,
+            scale,
+            &Self::palette(palette),
+        )
+    }
+}
+
+/// Parses a hex address range like `200-400`, end excluded.
+pub fn parse_region(value: &str) -> Result<Range<usize>, String> {
+    let error = || format!("expected a hex range like `200-400`, got `{}`", value);
+    let (start, end) = value.split_once('-').ok_or_else(error)?;
+    let start = usize::from_str_radix(start.trim(), 16).map_err(|_| error())?;
+    let end = usize::from_str_radix(end.trim(), 16).map_err(|_| error())?;
+    if start >= end {
+        return Err(error());
+    }
+    Ok(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bytes_fill_columns_then_bands() {
+        assert_eq!(SpriteLayout::Chip8.position(15), (0, 15));
+        assert_eq!(SpriteLayout::Chip8.position(16), (9, 0));
+        assert_eq!(SpriteLayout::Chip8.position(256), (0, 17));
+        assert_eq!(SpriteLayout::Schip.position(1), (8, 0));
+        assert_eq!(SpriteLayout::Schip.position(31), (8, 15));
+        assert_eq!(SpriteLayout::Schip.position(32), (17, 0));
+    }
+
+    #[test]
+    fn sheet_draws_bits_highlights_and_gaps() {
+        let mut memory = [0; 0x1000];
+        memory[0x300] = 0x80;
+        memory[0x301] = 0x01;
+        let sheet = SpriteSheet::new(
+            &memory,
+            0x300..0x302,
+            SpriteLayout::Chip8,
+            Some(0x301..0x302),
+        );
+        assert_eq!((sheet.width, sheet.height), (143, 16));
+        let pixel = |x: usize, y: usize| sheet.pixels[y * sheet.width + x];
+        assert_eq!([pixel(0, 0), pixel(1, 0), pixel(8, 0)], [ON, OFF, GAP]);
+        assert_eq!([pixel(0, 1), pixel(7, 1)], [HIGHLIGHT_OFF, HIGHLIGHT_ON]);
+        assert_eq!(pixel(0, 2), GAP);
+
+        let two_bands = SpriteSheet::new(&memory, 0x300..0x401, SpriteLayout::Chip8, None);
+        assert_eq!(two_bands.height, 2 * BAND_HEIGHT - 1);
+    }
+
+    #[test]
+    fn parse_region_takes_hex_ranges() {
+        assert_eq!(parse_region("200-400"), Ok(0x200..0x400));
+        assert!(parse_region("400-200").is_err());
+        assert!(parse_region("200").is_err());
+        assert!(parse_region("xyz-400").is_err());
+    }
+}
@@ -1,30 +1,33 @@
+use std::time::{Duration, Instant};
+
 pub struct Timers {
-    pub last_time: std::time::Instant,
-    pub next_update: std::time::Duration,
-    pub delay_timer: u8,
-    pub sound_timer: u8,
+    pub next_frame: Instant,
+    pub frame_duration: Duration,
 }
 
 impl Timers {
     pub fn new() -> Self {
         Timers {
-            last_time: std::time::Instant::now(),
-            next_update: std::time::Duration::from_millis(17),
-            delay_timer: 0,
-            sound_timer: 0,
+            next_frame: Instant::now(),
+            frame_duration: Duration::from_nanos(1_000_000_000 / 60),
         }
     }
 
-    pub fn update(&mut self) {
-        let now = std::time::Instant::now();
-        if now - self.last_time > self.next_update {
-            self.last_time = now;
-            if self.delay_timer > 0 {
-                self.delay_timer -= 1;
-            }
-            if self.sound_timer > 0 {
-                self.sound_timer -= 1;
-            }
+    /// Sleeps until the next frame is due, or resynchronizes if running behind.
+    /// `speed` scales the frame rate, so 0.5 runs at 30 frames per second.
+    pub fn wait_for_next_frame(&mut self, speed: f64) {
+        self.next_frame += self.frame_duration.div_f64(speed);
+        let now = Instant::now();
+        if self.next_frame > now {
+            std::thread::sleep(self.next_frame - now);
+        } else {
+            self.next_frame = now;
         }
     }
 }
+
+impl Default for Timers {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,89 @@
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::ops::Shr;
+
+pub fn disasm_chip_8_op(memory: &[u8], pc: usize) -> String {
+    let first_part = memory[pc];
+    let second_part = memory[pc + 1];
+    let whole = (first_part as u16) << 8 | second_part as u16;
+    let quad_1: u8 = first_part.shr(4);
+    let quad_2: u8 = first_part & 0x0F;
+    let quad_3: u8 = second_part.shr(4);
+    let quad_4: u8 = second_part & 0x0F;
+    let address = ((quad_2 as u16) << 8) | (second_part as u16);
+    let number = quad_3 << 4 | quad_4;
+
+    match quad_1 {
+        0x00 => match address {
+            0x00E0 => "CLS".to_string(),
+            0x00EE => "RET".to_string(),
+            _ => format!("SYS addr: {:0x}", address),
+        },
+        0x01 => format!("JMP {:0x}", address),
+        0x02 => format!("CALL {:0x}", address),
+        0x03 => format!("SKIP IF V{:x} == {:0x}", address, number),
+        0x04 => format!("SKIP IF V{:x} != {:0x}", address, number),
+        0x05 => format!("SKIP IF V{:x} == V{:x}", quad_2, quad_3),
+        0x06 => format!("STORE V{:x}, {:0x}", quad_2, number),
+        0x07 => format!("ADD V{:x}, {:0x}", quad_2, number),
+        0x08 => match quad_4 {
+            0x00 => format!("LD V{:x}, V{:x}", quad_2, quad_3),
+            0x01 => format!("OR V{:x}, V{:x}", quad_2, quad_3),
+            0x02 => format!("AND V{:x}, V{:x}", quad_2, quad_3),
+            0x03 => format!("XOR V{:x}, V{:x}", quad_2, quad_3),
+            0x04 => format!("ADD V{:x}, V{:x}; VF = C", quad_2, quad_3),
+            0x05 => format!("SUB V{:x}, V{:x}; VF = C", quad_2, quad_3),
+            0x06 => format!("SHR V{:x}, V{:x}, 1; VF = LSB(V{1:x})", quad_2, quad_3),
+            0x07 => format!("STORE V{:x}, V{:x} - V{0:x}, VF = C", quad_2, quad_3),
+            0x0E => format!("SHL V{:x}, V{:x}, 1; VF = MSB(V{1:x})", quad_2, quad_3),
+            _ => format!("UNSUPPORTED INSTRUCTION: {:0x}", whole),
+        },
+        0x09 => format!("SKIP IF V{:x} != V{:x}", quad_2, quad_3),
+        0x0A => format!("STORE I, {:0x}", address),
+        0x0B => format!("JMP {:0x} + V0", address),
+        0x0C => format!("STORE V{:x}, RAND() & {:0x}", quad_2, number),
+        0x0D => format!("DRAW V{:x}, V{:x}, {:0x}", quad_2, quad_3, quad_4),
+        0x0E => match number {
+            0x9E => format!("SKIP IF IS_PUSHED(V{:x})", quad_2),
+            0xA1 => format!("SKIP IF !IS_PUSHED(V{:x})", quad_2),
+            _ => format!("UNSUPPORTED INSTRUCTION: {:0x}", whole),
+        },
+        0xF => match number {
+            0x07 => format!("STORE V{:x}, DELAY_TIMER", quad_2),
+            0x0A => format!("AWAIT KEY V{:x}", quad_2),
+            0x15 => format!("STORE DELAY_TIMER, V{:x}", quad_2),
+            0x18 => format!("STORE SOUND_TIMER, V{:x}", quad_2),
+            0x1E => format!("ADD I, V{:x}", quad_2),
+            0x29 => format!("STORE I, sprite_addr[V{:x}]", quad_2),
+            0x33 => format!("STORE [I], [I+1],[I+1], BCD(V{:x})", quad_2),
+            0x55 => format!(
+                "REG DUMP FROM V0..=V{:x} to [I]; I = I + {0:0x} + 1",
+                quad_2
+            ),
+            0x65 => format!(
+                "REG LOAD FROM V0..=V{:x} from [I]; I = I + {0:0x} + 1",
+                quad_2
+            ),
+            _ => format!("UNSUPPORTED INSTRUCTION: {:0x}", whole),
+        },
+        _ => format!("UNSUPPORTED INSTRUCTION: {:0x}", whole),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassembles_register_moves_and_names_unknown_opcodes() {
+        assert_eq!(disasm_chip_8_op(&[0x83, 0xA0], 0), "LD V3, Va");
+        assert_eq!(
+            disasm_chip_8_op(&[0x83, 0xA8], 0),
+            "UNSUPPORTED INSTRUCTION: 83a8"
+        );
+        assert_eq!(
+            disasm_chip_8_op(&[0xE1, 0x00], 0),
+            "UNSUPPORTED INSTRUCTION: e100"
+        );
+    }
+}
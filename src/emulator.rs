@@ -0,0 +1,198 @@
+//! A machine together with its ROM, random source and keypad, stepped one frame at a
+//! time. The JavaScript, C and Python bindings are thin wrappers around [`Emulator`],
+//! so they behave the same and share one snapshot format.
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::machine::{Fault, Machine, Quirks, RomTooLarge, StateError, XorShift, STATE_SIZE};
+
+/// Size of a snapshot: the machine state followed by the random source.
+pub const SNAPSHOT_SIZE: usize = STATE_SIZE + 8;
+
+/// Instructions per frame of a new emulator.
+pub const DEFAULT_TICKRATE: u32 = 10;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum LoadRomError {
+    Empty,
+    TooLarge(RomTooLarge),
+}
+
+impl fmt::Display for LoadRomError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadRomError::Empty => write!(f, "ROM is empty"),
+            LoadRomError::TooLarge(err) => err.fmt(f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for LoadRomError {}
+
+pub struct Emulator {
+    machine: Machine,
+    rom: Vec<u8>,
+    random: XorShift,
+    /// Instructions executed per frame.
+    pub tickrate: u32,
+    /// Keys held, handed to the machine at the next step.
+    keys: u16,
+}
+
+impl Emulator {
+    /// An emulator with no ROM loaded; `seed` drives CXNN, so equal seeds and inputs
+    /// replay a game exactly.
+    pub fn new(quirks: Quirks, seed: u64) -> Self {
+        Emulator {
+            machine: Machine::new(quirks),
+            rom: Vec::new(),
+            random: XorShift::new(seed),
+            tickrate: DEFAULT_TICKRATE,
+            keys: 0,
+        }
+    }
+
+    /// Loads a ROM and resets. On error the previous ROM stays loaded, untouched.
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), LoadRomError> {
+        if rom.is_empty() {
+            return Err(LoadRomError::Empty);
+        }
+        self.machine.reset(rom).map_err(LoadRomError::TooLarge)?;
+        self.rom = rom.to_vec();
+        self.keys = 0;
+        Ok(())
+    }
+
+    /// Restarts the loaded ROM and releases all keys.
+    pub fn reset(&mut self) {
+        // `load_rom` only keeps ROMs that fit.
+        let _ = self.machine.reset(&self.rom);
+        self.keys = 0;
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        &self.rom
+    }
+
+    pub fn machine(&self) -> &Machine {
+        &self.machine
+    }
+
+    pub fn machine_mut(&mut self) -> &mut Machine {
+        &mut self.machine
+    }
+
+    /// Presses or releases keypad key `key`; keys above F are ignored.
+    pub fn set_key(&mut self, key: u8, down: bool) {
+        if key >= 16 {
+            return;
+        }
+        if down {
+            self.keys |= 1 << key;
+        } else {
+            self.keys &= !(1 << key);
+        }
+    }
+
+    /// Held keys as a mask, bit N = key N.
+    pub fn keys(&self) -> u16 {
+        self.keys
+    }
+
+    pub fn set_keys(&mut self, keys: u16) {
+        self.keys = keys;
+    }
+
+    /// Runs one 60 Hz frame: `tickrate` instructions, then a timer tick. Does nothing
+    /// until a ROM is loaded.
+    pub fn step_frame(&mut self) -> Result<(), Fault> {
+        if self.rom.is_empty() {
+            return Ok(());
+        }
+        self.machine.set_keys(self.keys);
+        self.machine.step_frame(self.tickrate, &mut self.random)
+    }
+
+    /// Runs `cycles` instructions without touching the timers.
+    pub fn step_cycles(&mut self, cycles: u32) -> Result<(), Fault> {
+        if self.rom.is_empty() {
+            return Ok(());
+        }
+        if self.keys != self.machine.keys() {
+            self.machine.set_keys(self.keys);
+        }
+        for _ in 0..cycles {
+            self.machine.cycle(&mut self.random)?;
+        }
+        Ok(())
+    }
+
+    /// Everything needed to resume exactly here, random source included.
+    pub fn snapshot(&self) -> [u8; SNAPSHOT_SIZE] {
+        let mut snapshot = [0; SNAPSHOT_SIZE];
+        snapshot[..STATE_SIZE].copy_from_slice(&self.machine.save_state());
+        snapshot[STATE_SIZE..].copy_from_slice(&self.random.0.to_le_bytes());
+        snapshot
+    }
+
+    /// Goes back to a `snapshot`; nothing changes if it is not a valid one.
+    pub fn restore(&mut self, snapshot: &[u8]) -> Result<(), StateError> {
+        if snapshot.len() != SNAPSHOT_SIZE {
+            return Err(StateError::WrongSize {
+                size: snapshot.len(),
+                expected: SNAPSHOT_SIZE,
+            });
+        }
+        self.machine.load_state(&snapshot[..STATE_SIZE])?;
+        let mut random = [0; 8];
+        random.copy_from_slice(&snapshot[STATE_SIZE..]);
+        self.random = XorShift::new(u64::from_le_bytes(random));
+        self.keys = self.machine.keys();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// V0 = random byte, V1 += 1, loop.
+    const RANDOM_LOOP: [u8; 6] = [0xC0, 0xFF, 0x71, 0x01, 0x12, 0x00];
+
+    #[test]
+    fn rejects_roms_it_cannot_run_and_keeps_the_old_one() {
+        let mut emulator = Emulator::new(Quirks::default(), 1);
+        assert_eq!(emulator.load_rom(&[]), Err(LoadRomError::Empty));
+        emulator.load_rom(&RANDOM_LOOP).unwrap();
+        let too_large = [0; crate::machine::MAX_ROM_SIZE + 1];
+        assert!(matches!(
+            emulator.load_rom(&too_large),
+            Err(LoadRomError::TooLarge(_))
+        ));
+        assert_eq!(emulator.rom(), RANDOM_LOOP);
+    }
+
+    #[test]
+    fn restoring_a_snapshot_replays_the_same_frames() {
+        let mut emulator = Emulator::new(Quirks::default(), 7);
+        emulator.load_rom(&RANDOM_LOOP).unwrap();
+        emulator.set_key(3, true);
+        emulator.step_frame().unwrap();
+        let snapshot = emulator.snapshot();
+        emulator.step_frame().unwrap();
+        let expected = emulator.machine().registers;
+
+        emulator.reset();
+        assert_eq!(emulator.keys(), 0);
+        emulator.restore(&snapshot).unwrap();
+        assert_eq!(emulator.keys(), 1 << 3);
+        emulator.step_frame().unwrap();
+        assert_eq!(emulator.machine().registers, expected);
+        assert!(matches!(
+            emulator.restore(&snapshot[1..]),
+            Err(StateError::WrongSize { .. })
+        ));
+    }
+}
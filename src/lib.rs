@@ -0,0 +1,28 @@
+//! A CHIP-8 interpreter.
+//!
+//! [`machine::Machine`] is the bare CHIP-8 and builds without `std` (and without
+//! `alloc`, which adds [`disasm`] and the [`emulator::Emulator`] the bindings wrap).
+//! With the `std` feature, [`Chip8`] runs a ROM one 60 Hz frame at a time on a
+//! [`screen::Backend`]: an SDL window (with the `sdl` feature), the terminal, nothing
+//! at all, or one of your own. The `wasm` feature adds JavaScript bindings in [`wasm`].
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "std")]
+mod chip8;
+#[cfg(feature = "alloc")]
+pub mod disasm;
+#[cfg(feature = "alloc")]
+pub mod emulator;
+pub mod machine;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+
+#[cfg(feature = "std")]
+pub use chip8::{
+    cheats, config, env, keymap, launcher, movie, options, profiler, recorder, rom, romdb, screen,
+    screenshot, sprites, timers, Chip8,
+};
@@ -0,0 +1,2 @@
+pub static STACK_POINT_START: usize = 0x52;
+pub static PC_START: usize = 0x200;
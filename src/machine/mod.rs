@@ -0,0 +1,1004 @@
+//! The CHIP-8 itself: memory, registers, timers, keypad and display, and the
+//! instruction decoder. It needs neither `std` nor `alloc`, so the host drives it:
+//! [`Machine::cycle`] runs one instruction with random bytes from the host,
+//! [`Machine::tick_timers`] is called 60 times per second, and
+//! [`Machine::set_keys`] whenever the keypad changes.
+
+use core::fmt;
+use core::ops::{Range, Shr};
+#[cfg(feature = "alloc")]
+use core::str::FromStr;
+
+#[cfg(feature = "alloc")]
+use alloc::{format, string::String};
+
+pub(crate) mod cpu_const;
+mod state;
+
+pub use state::{StateError, STATE_SIZE};
+
+pub const WIDTH: usize = 64;
+pub const HEIGHT: usize = 32;
+pub const MEMORY_SIZE: usize = 4096;
+/// Largest program that fits between the program start and the end of memory.
+pub const MAX_ROM_SIZE: usize = MEMORY_SIZE - cpu_const::PC_START;
+
+/// A ROM that does not fit between the program start and the end of memory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RomTooLarge(pub usize);
+
+impl fmt::Display for RomTooLarge {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "ROM is {} bytes, but at most {} bytes fit in CHIP-8 memory",
+            self.0, MAX_ROM_SIZE
+        )
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RomTooLarge {}
+
+/// Why the machine stopped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FaultKind {
+    /// An opcode this machine does not implement.
+    UnknownOpcode,
+    /// PC, or memory read or written from I, went past the end of memory.
+    OutOfMemory,
+    /// 2NNN with the stack full up to the program start.
+    StackOverflow,
+    /// 00EE with nothing on the stack.
+    StackUnderflow,
+}
+
+/// An instruction the machine could not execute. The machine halts on it, with PC
+/// left on the instruction, until it is reset or a state is loaded.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Fault {
+    pub kind: FaultKind,
+    pub address: usize,
+    pub opcode: u16,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let reason = match self.kind {
+            FaultKind::UnknownOpcode => "unknown instruction",
+            FaultKind::OutOfMemory => "memory access out of range by",
+            FaultKind::StackOverflow => "stack overflow in",
+            FaultKind::StackUnderflow => "stack underflow in",
+        };
+        write!(f, "{} {:04X} at {:03X}", reason, self.opcode, self.address)
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Fault {}
+
+/// Behaviours that differ between CHIP-8 interpreters. Apart from `key_wait_press`
+/// they are the quirks of the chip-8-database, and with all of them off the machine
+/// behaves like most interpreters (the database's `modernChip8`).
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Quirks {
+    /// FX0A resumes as soon as a key goes down instead of waiting for its release.
+    pub key_wait_press: bool,
+    /// 8XY6 and 8XYE shift VX in place instead of shifting VY into VX.
+    pub shift: bool,
+    /// FX55 and FX65 leave I at I + X instead of I + X + 1.
+    pub memory_increment_by_x: bool,
+    /// FX55 and FX65 leave I unchanged.
+    pub memory_leave_i_unchanged: bool,
+    /// Sprites wrap around the edges of the display instead of being clipped.
+    pub wrap: bool,
+    /// BNNN jumps to NNN + VX, X being the high digit of NNN, instead of NNN + V0.
+    pub jump: bool,
+    /// DXYN waits for the next timer tick once a sprite was drawn in the frame, so
+    /// at most one is drawn per frame.
+    pub vblank: bool,
+    /// 8XY1, 8XY2 and 8XY3 reset VF to 0.
+    pub logic: bool,
+}
+
+/// Quirk names as `Display` and `FromStr` spell them, in `Quirks::bits` order.
+const QUIRK_NAMES: [&str; 8] = [
+    "key-wait-press",
+    "shift",
+    "memory-increment-by-x",
+    "memory-leave-i-unchanged",
+    "wrap",
+    "jump",
+    "vblank",
+    "logic",
+];
+
+impl Quirks {
+    /// The quirks as flags, bit N for `QUIRK_NAMES[N]`.
+    pub fn bits(self) -> u8 {
+        [
+            self.key_wait_press,
+            self.shift,
+            self.memory_increment_by_x,
+            self.memory_leave_i_unchanged,
+            self.wrap,
+            self.jump,
+            self.vblank,
+            self.logic,
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |bits, (bit, &on)| bits | (on as u8) << bit)
+    }
+
+    pub fn from_bits(bits: u8) -> Self {
+        let on = |bit: usize| bits & 1 << bit != 0;
+        Quirks {
+            key_wait_press: on(0),
+            shift: on(1),
+            memory_increment_by_x: on(2),
+            memory_leave_i_unchanged: on(3),
+            wrap: on(4),
+            jump: on(5),
+            vblank: on(6),
+            logic: on(7),
+        }
+    }
+
+    /// Every quirk switched on in either.
+    pub fn union(self, other: Quirks) -> Self {
+        Quirks::from_bits(self.bits() | other.bits())
+    }
+}
+
+/// Formats the enabled quirks as a comma separated list of their names.
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut separator = "";
+        for (bit, name) in QUIRK_NAMES.iter().enumerate() {
+            if self.bits() & 1 << bit != 0 {
+                write!(f, "{}{}", separator, name)?;
+                separator = ",";
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl FromStr for Quirks {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bits = 0;
+        for name in s.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+            let bit = QUIRK_NAMES
+                .iter()
+                .position(|&quirk| quirk == name)
+                .ok_or_else(|| format!("unknown quirk `{}`", name))?;
+            bits |= 1 << bit;
+        }
+        Ok(Quirks::from_bits(bits))
+    }
+}
+
+/// Where CXNN gets its random bytes from; any `FnMut() -> u8` will do.
+pub trait Random {
+    fn random_byte(&mut self) -> u8;
+}
+
+impl<F: FnMut() -> u8> Random for F {
+    fn random_byte(&mut self) -> u8 {
+        self()
+    }
+}
+
+/// A small xorshift generator for hosts without a random source of their own. The
+/// field is its state, to save alongside the machine's.
+#[derive(Clone, Copy)]
+pub struct XorShift(pub u64);
+
+impl XorShift {
+    pub fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift never leaves.
+        XorShift(seed.max(1))
+    }
+}
+
+impl Random for XorShift {
+    fn random_byte(&mut self) -> u8 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        (self.0 >> 56) as u8
+    }
+}
+
+pub struct Machine {
+    pub memory: [u8; MEMORY_SIZE],
+    pub registers: [u8; 16],
+    pub i: u16,
+    pub pc: usize,
+    pub sp: usize,
+    pub delay_timer: u8,
+    pub sound_timer: u8,
+    pub quirks: Quirks,
+    /// One byte per pixel, 0 or 1, row by row.
+    pub display: [u8; WIDTH * HEIGHT],
+    /// Held keys as a mask, bit N = key N.
+    keys: u16,
+    last_keys: u16,
+    /// Key pressed during FX0A, stored once it is released again.
+    awaited_key: Option<u8>,
+    wainting: bool,
+    /// Set when a sprite was drawn since the last timer tick, for the vblank quirk.
+    drawn: bool,
+    last_pc: usize,
+    whole: u16,
+    cycles: usize,
+    /// Set when the display changed since the last `take_redraw`.
+    redraw: bool,
+    /// Memory written by the last instruction.
+    written: Option<Range<usize>>,
+    /// Memory read by the last DXYN.
+    sprite: Option<Range<usize>>,
+    fault: Option<Fault>,
+}
+
+impl Machine {
+    /// A machine in its power-on state with the font loaded and no ROM; load one
+    /// with `reset`.
+    pub fn new(quirks: Quirks) -> Self {
+        let mut machine = Machine {
+            memory: [0; MEMORY_SIZE],
+            registers: [0; 16],
+            i: 0,
+            pc: cpu_const::PC_START,
+            sp: cpu_const::STACK_POINT_START,
+            delay_timer: 0,
+            sound_timer: 0,
+            quirks,
+            display: [0; WIDTH * HEIGHT],
+            keys: 0,
+            last_keys: 0,
+            awaited_key: None,
+            wainting: false,
+            drawn: false,
+            last_pc: 0,
+            whole: 0,
+            cycles: 0,
+            redraw: true,
+            written: None,
+            sprite: None,
+            fault: None,
+        };
+        machine.power_on();
+        machine
+    }
+
+    /// Puts registers, stack, timers, keys and the display back in their power-on
+    /// state and copies the font and `rom` into fresh memory. A ROM too large for
+    /// memory leaves the machine untouched.
+    pub fn reset(&mut self, rom: &[u8]) -> Result<(), RomTooLarge> {
+        if rom.len() > MAX_ROM_SIZE {
+            return Err(RomTooLarge(rom.len()));
+        }
+        self.power_on();
+        self.memory[cpu_const::PC_START..cpu_const::PC_START + rom.len()].copy_from_slice(rom);
+        Ok(())
+    }
+
+    fn power_on(&mut self) {
+        self.memory = [0; MEMORY_SIZE];
+        self.registers = [0; 16];
+        self.i = 0;
+        self.pc = cpu_const::PC_START;
+        self.last_pc = 0;
+        self.sp = cpu_const::STACK_POINT_START;
+        self.delay_timer = 0;
+        self.sound_timer = 0;
+        self.cycles = 0;
+        self.whole = 0;
+        self.wainting = false;
+        self.drawn = false;
+        self.awaited_key = None;
+        self.keys = 0;
+        self.last_keys = 0;
+        let font = include_bytes!("../../FONTS.chip8");
+        self.memory[..font.len()].copy_from_slice(font);
+        self.display.fill(0);
+        self.redraw = true;
+        self.written = None;
+        self.sprite = None;
+        self.fault = None;
+    }
+
+    /// Runs one 60 Hz frame: `tickrate` instructions, then one timer tick. Stops
+    /// early if an instruction faults.
+    pub fn step_frame(&mut self, tickrate: u32, random: &mut impl Random) -> Result<(), Fault> {
+        for _ in 0..tickrate {
+            self.cycle(random)?;
+        }
+        self.tick_timers();
+        Ok(())
+    }
+
+    /// Replaces the held keys for the instructions that follow; bit N = key N.
+    pub fn set_keys(&mut self, keys: u16) {
+        self.last_keys = core::mem::replace(&mut self.keys, keys);
+    }
+
+    pub fn keys(&self) -> u16 {
+        self.keys
+    }
+
+    /// Counts both timers down by one 60 Hz frame.
+    pub fn tick_timers(&mut self) {
+        self.delay_timer = self.delay_timer.saturating_sub(1);
+        self.sound_timer = self.sound_timer.saturating_sub(1);
+        self.drawn = false;
+    }
+
+    pub fn sound_on(&self) -> bool {
+        self.sound_timer > 0
+    }
+
+    /// Whether the display changed since the last call.
+    pub fn take_redraw(&mut self) -> bool {
+        core::mem::take(&mut self.redraw)
+    }
+
+    /// Address of the instruction executed last.
+    pub fn last_pc(&self) -> usize {
+        self.last_pc
+    }
+
+    /// The instruction executed last.
+    pub fn opcode(&self) -> u16 {
+        self.whole
+    }
+
+    /// Instructions executed since the last reset, not counting FX0A while it waits.
+    pub fn cycles(&self) -> usize {
+        self.cycles
+    }
+
+    /// The memory the last instruction wrote to, if any: the stack for 2NNN, and
+    /// the bytes from I on for FX33 and FX55.
+    pub fn last_write(&self) -> Option<Range<usize>> {
+        self.written.clone()
+    }
+
+    /// The sprite bytes drawn by the last DXYN since the reset, if any.
+    pub fn last_sprite(&self) -> Option<Range<usize>> {
+        self.sprite.clone()
+    }
+
+    /// The fault the machine halted on, if any.
+    pub fn fault(&self) -> Option<Fault> {
+        self.fault
+    }
+
+    /// Whether FX0A is blocked waiting for a key.
+    pub fn waiting(&self) -> bool {
+        self.wainting
+    }
+
+    /// Executes the instruction at PC. Once an instruction faults, the machine stays
+    /// halted on it and every call returns the same fault.
+    pub fn cycle(&mut self, random: &mut impl Random) -> Result<(), Fault> {
+        if let Some(fault) = self.fault {
+            return Err(fault);
+        }
+        if self.pc + 1 >= MEMORY_SIZE {
+            self.last_pc = self.pc;
+            self.whole = 0;
+            return Err(self.halt(FaultKind::OutOfMemory));
+        }
+        let first_part = self.memory[self.pc];
+        let second_part = self.memory[self.pc + 1];
+        self.whole = (first_part as u16) << 8 | second_part as u16;
+        let disc_1: u8 = first_part.shr(4);
+        let reg_1: u8 = first_part & 0x0F;
+        let reg_2: u8 = second_part.shr(4);
+        let disc_2: u8 = second_part & 0x0F;
+        let address = ((reg_1 as u16) << 8) | (second_part as u16);
+        let number = reg_2 << 4 | disc_2;
+        self.last_pc = self.pc;
+        self.pc += 2;
+        self.written = None;
+        if !self.wainting {
+            self.cycles += 1;
+        }
+        match disc_1 {
+            0x00 => self.clear_return(address),
+            0x01 => self.jump_to_address(address),
+            0x02 => self.call_subroutine(address),
+            0x03 => self.skip_if_reg_equal_val(number, reg_1),
+            0x04 => self.skip_if_reg_not_equal_val(number, reg_1),
+            0x05 => self.skip_if_reg_equal_reg(reg_1, reg_2),
+            0x06 => self.move_value_to_reg(reg_1, number),
+            0x07 => self.add_value_to_reg(reg_1, number),
+            0x08 => self.execute_logical_instruction(reg_1, reg_2, disc_2),
+            0x09 => self.skip_if_reg_not_equal_reg(reg_1, reg_2),
+            0x0A => self.load_index_reg_with_value(address),
+            0x0B => self.jump_to_register_plus_value(reg_1, address),
+            0x0C => self.generate_random_number(reg_1, number, random),
+            0x0D => self.draw_sprite(reg_1, reg_2, disc_2),
+            0x0E => self.keyboard_routines(reg_1, number),
+            _ => self.misc_routines(reg_1, number),
+        }
+        if let Some(fault) = self.fault {
+            self.pc = self.last_pc;
+            return Err(fault);
+        }
+        if self.wainting {
+            self.pc = self.last_pc;
+        }
+        Ok(())
+    }
+
+    /// Stops the machine on the current instruction.
+    fn halt(&mut self, kind: FaultKind) -> Fault {
+        let fault = Fault {
+            kind,
+            address: self.last_pc,
+            opcode: self.whole,
+        };
+        self.fault = Some(fault);
+        fault
+    }
+
+    /// Whether `len` bytes from I are in memory; halts the machine if not.
+    fn index_in_memory(&mut self, len: usize) -> bool {
+        if self.i as usize + len > MEMORY_SIZE {
+            self.halt(FaultKind::OutOfMemory);
+            return false;
+        }
+        true
+    }
+
+    fn key_down(&self, key: u8) -> bool {
+        key < 16 && self.keys & 1 << key != 0
+    }
+}
+
+impl Machine {
+    fn clear_return(&mut self, address: u16) {
+        match address {
+            0x00E0 => {
+                self.display.fill(0);
+                self.redraw = true;
+            }
+            0x00EE => {
+                if self.sp < cpu_const::STACK_POINT_START + 2 {
+                    self.halt(FaultKind::StackUnderflow);
+                    return;
+                }
+                self.sp -= 1;
+                self.pc = (self.memory[self.sp] as usize) << 8 | self.memory[self.sp - 1] as usize;
+                self.sp -= 1;
+            }
+            _ => self.pc = address as usize,
+        }
+    }
+    fn jump_to_address(&mut self, address: u16) {
+        self.pc = address as usize;
+    }
+    fn call_subroutine(&mut self, address: u16) {
+        if self.sp + 2 > cpu_const::PC_START {
+            self.halt(FaultKind::StackOverflow);
+            return;
+        }
+        self.written = Some(self.sp..self.sp + 2);
+        self.memory[self.sp] = self.pc as u8;
+        self.sp += 1;
+        self.memory[self.sp] = (self.pc.shr(8)) as u8;
+        self.sp += 1;
+        self.pc = address as usize;
+    }
+    fn skip_if_reg_equal_val(&mut self, number: u8, reg: u8) {
+        if self.registers[reg as usize] == number {
+            self.pc += 2;
+        }
+    }
+    fn skip_if_reg_not_equal_val(&mut self, number: u8, reg: u8) {
+        if self.registers[reg as usize] != number {
+            self.pc += 2;
+        }
+    }
+    fn skip_if_reg_equal_reg(&mut self, reg_1: u8, reg_2: u8) {
+        if self.registers[reg_1 as usize] == self.registers[reg_2 as usize] {
+            self.pc += 2;
+        }
+    }
+    fn move_value_to_reg(&mut self, reg: u8, number: u8) {
+        self.registers[reg as usize] = number;
+    }
+    fn add_value_to_reg(&mut self, reg: u8, number: u8) {
+        self.registers[reg as usize] = self.registers[reg as usize].wrapping_add(number);
+    }
+    fn execute_logical_instruction(&mut self, reg_1: u8, reg_2: u8, disc: u8) {
+        match disc {
+            0x00 => self.registers[reg_1 as usize] = self.registers[reg_2 as usize],
+            0x01..=0x03 => {
+                let y = self.registers[reg_2 as usize];
+                match disc {
+                    0x01 => self.registers[reg_1 as usize] |= y,
+                    0x02 => self.registers[reg_1 as usize] &= y,
+                    _ => self.registers[reg_1 as usize] ^= y,
+                }
+                if self.quirks.logic {
+                    self.registers[0xF] = 0;
+                }
+            }
+            0x04 => {
+                let result =
+                    self.registers[reg_1 as usize] as u16 + self.registers[reg_2 as usize] as u16;
+                self.registers[0xF] = if result > 0xFF { 1 } else { 0 };
+                self.registers[reg_1 as usize] = result as u8;
+            }
+            0x05 => {
+                self.registers[0xF] =
+                    if self.registers[reg_1 as usize] > self.registers[reg_2 as usize] {
+                        1
+                    } else {
+                        0
+                    };
+                self.registers[reg_1 as usize] =
+                    self.registers[reg_1 as usize].wrapping_sub(self.registers[reg_2 as usize]);
+            }
+            0x06 => {
+                let value = self.shift_source(reg_1, reg_2);
+                self.registers[reg_1 as usize] = value >> 1;
+                self.registers[0xF] = value & 0x01;
+            }
+            0x07 => {
+                self.registers[0xF] =
+                    if self.registers[reg_2 as usize] > self.registers[reg_1 as usize] {
+                        1
+                    } else {
+                        0
+                    };
+                self.registers[reg_1 as usize] =
+                    self.registers[reg_2 as usize].wrapping_sub(self.registers[reg_1 as usize]);
+            }
+            0x0E => {
+                let value = self.shift_source(reg_1, reg_2);
+                self.registers[reg_1 as usize] = value << 1;
+                self.registers[0xF] = value >> 7;
+            }
+            _ => {
+                self.halt(FaultKind::UnknownOpcode);
+            }
+        }
+    }
+    /// What 8XY6 and 8XYE shift: VX with the shift quirk, VY without.
+    fn shift_source(&self, reg_1: u8, reg_2: u8) -> u8 {
+        if self.quirks.shift {
+            self.registers[reg_1 as usize]
+        } else {
+            self.registers[reg_2 as usize]
+        }
+    }
+    fn skip_if_reg_not_equal_reg(&mut self, reg_1: u8, reg_2: u8) {
+        if self.registers[reg_1 as usize] != self.registers[reg_2 as usize] {
+            self.pc += 2;
+        }
+    }
+    fn load_index_reg_with_value(&mut self, address: u16) {
+        self.i = address;
+    }
+    fn jump_to_register_plus_value(&mut self, reg: u8, adress: u16) {
+        let reg = if self.quirks.jump { reg } else { 0 };
+        self.pc = (self.registers[reg as usize] as u16 + adress) as usize;
+    }
+    fn generate_random_number(&mut self, reg: u8, mask: u8, random: &mut impl Random) {
+        self.registers[reg as usize] = random.random_byte() & mask;
+    }
+    fn draw_sprite(&mut self, x: u8, y: u8, len: u8) {
+        if self.quirks.vblank && self.drawn {
+            self.pc = self.last_pc;
+            return;
+        }
+        if !self.index_in_memory(len as usize) {
+            return;
+        }
+        self.drawn = true;
+        self.sprite = Some(self.i as usize..self.i as usize + len as usize);
+        let sprite = &self.memory[self.i as usize..((self.i as usize) + len as usize)];
+        let x_start = self.registers[x as usize] as usize % WIDTH;
+        let y_start = self.registers[y as usize] as usize % HEIGHT;
+        let mut collision = false;
+
+        for (y_offset, byte) in sprite.iter().enumerate() {
+            for x_offset in 0..8 {
+                if (byte & (0x80 >> x_offset)) != 0 {
+                    let (x, y) = (x_start + x_offset, y_start + y_offset);
+                    if !self.quirks.wrap && (x >= WIDTH || y >= HEIGHT) {
+                        continue;
+                    }
+                    let index = x % WIDTH + y % HEIGHT * WIDTH;
+
+                    // Check for collision
+                    if self.display[index] == 1 {
+                        collision = true;
+                    }
+
+                    // XOR drawing
+                    self.display[index] ^= 1;
+                }
+            }
+        }
+
+        self.redraw = true;
+        self.registers[0xF] = if collision { 1 } else { 0 };
+    }
+    fn keyboard_routines(&mut self, reg: u8, disc: u8) {
+        match disc {
+            0x9E => {
+                if self.key_down(self.registers[reg as usize]) {
+                    self.pc += 2;
+                }
+            }
+            0xA1 => {
+                if !self.key_down(self.registers[reg as usize]) {
+                    self.pc += 2;
+                }
+            }
+            _ => {
+                self.halt(FaultKind::UnknownOpcode);
+            }
+        }
+    }
+    fn wait_for_key(&mut self, reg: u8) {
+        self.wainting = true;
+        let key = match self.awaited_key {
+            Some(key) if self.key_down(key) => return,
+            Some(key) => key,
+            None => {
+                let Some(key) =
+                    (0..16).find(|&key| self.key_down(key) && self.last_keys & 1 << key == 0)
+                else {
+                    return;
+                };
+                if !self.quirks.key_wait_press {
+                    self.awaited_key = Some(key);
+                    return;
+                }
+                key
+            }
+        };
+        self.awaited_key = None;
+        self.wainting = false;
+        self.registers[reg as usize] = key;
+        // Keys are sampled once per frame, so without this the same press would
+        // satisfy every FX0A until the next `set_keys`.
+        self.last_keys |= 1 << key;
+    }
+    /// Moves I past the registers FX55 and FX65 stored or loaded, as the quirks say.
+    fn advance_index(&mut self, reg: u8) {
+        if self.quirks.memory_leave_i_unchanged {
+            return;
+        }
+        let step = if self.quirks.memory_increment_by_x {
+            reg
+        } else {
+            reg + 1
+        };
+        self.i += step as u16;
+    }
+    fn misc_routines(&mut self, reg: u8, disc: u8) {
+        match disc {
+            0x07 => self.registers[reg as usize] = self.delay_timer,
+            0x0A => self.wait_for_key(reg),
+            0x15 => self.delay_timer = self.registers[reg as usize],
+            0x18 => self.sound_timer = self.registers[reg as usize],
+            0x1E => self.i = self.i.wrapping_add(self.registers[reg as usize] as u16),
+            0x29 => self.i = self.registers[reg as usize] as u16 * 5,
+            0x33 if !self.index_in_memory(3) => {}
+            0x33 => {
+                self.written = Some(self.i as usize..self.i as usize + 3);
+                let value = self.registers[reg as usize];
+                self.memory[self.i as usize] = value / 100;
+                self.memory[self.i as usize + 1] = (value / 10) % 10;
+                self.memory[self.i as usize + 2] = value % 10;
+            }
+            0x55 | 0x65 if !self.index_in_memory(reg as usize + 1) => {}
+            0x55 => {
+                self.written = Some(self.i as usize..self.i as usize + reg as usize + 1);
+                for i in 0..=reg {
+                    self.memory[self.i as usize + i as usize] = self.registers[i as usize];
+                }
+                self.advance_index(reg);
+            }
+            0x65 => {
+                for i in 0..=reg {
+                    self.registers[i as usize] = self.memory[self.i as usize + i as usize];
+                }
+                self.advance_index(reg);
+            }
+            _ => {
+                self.halt(FaultKind::UnknownOpcode);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// FX0A into V0, then V1 += 1 and loop, so V1 counts completed waits.
+    const KEY_WAIT_LOOP: [u8; 6] = [0xF0, 0x0A, 0x71, 0x01, 0x12, 0x00];
+
+    fn machine(rom: &[u8], quirks: Quirks) -> Machine {
+        let mut machine = Machine::new(quirks);
+        machine.reset(rom).unwrap();
+        machine
+    }
+
+    fn run_frame(machine: &mut Machine, keys: u16, tickrate: u32) {
+        machine.set_keys(keys);
+        machine.step_frame(tickrate, &mut || 0).unwrap();
+    }
+
+    #[test]
+    fn one_press_completes_one_key_wait() {
+        let quirks = Quirks {
+            key_wait_press: true,
+            ..Quirks::default()
+        };
+        let mut machine = machine(&KEY_WAIT_LOOP, quirks);
+        run_frame(&mut machine, 1 << 5, 30);
+        run_frame(&mut machine, 0, 30);
+        assert_eq!(machine.registers[1], 1);
+        assert_eq!(machine.registers[0], 5);
+    }
+
+    #[test]
+    fn key_wait_finishes_on_release_by_default() {
+        let mut machine = machine(&KEY_WAIT_LOOP, Quirks::default());
+        for _ in 0..3 {
+            run_frame(&mut machine, 1 << 0xA, 10);
+            assert!(machine.waiting());
+            assert_eq!(machine.registers[1], 0);
+        }
+        run_frame(&mut machine, 0, 10);
+        assert_eq!(machine.registers[1], 1);
+        assert_eq!(machine.registers[0], 0xA);
+        run_frame(&mut machine, 0, 10);
+        assert_eq!(machine.registers[1], 1);
+    }
+
+    #[test]
+    fn key_wait_press_finishes_on_press_once_while_held() {
+        let quirks = Quirks {
+            key_wait_press: true,
+            ..Quirks::default()
+        };
+        let mut machine = machine(&KEY_WAIT_LOOP, quirks);
+        run_frame(&mut machine, 1 << 3, 10);
+        assert_eq!(machine.registers[1], 1);
+        assert_eq!(machine.registers[0], 3);
+        for _ in 0..3 {
+            run_frame(&mut machine, 1 << 3, 10);
+        }
+        assert_eq!(machine.registers[1], 1);
+        run_frame(&mut machine, 0, 10);
+        run_frame(&mut machine, 1 << 3, 10);
+        assert_eq!(machine.registers[1], 2);
+    }
+
+    #[test]
+    fn key_wait_ignores_keys_held_before_it() {
+        for key_wait_press in [false, true] {
+            let mut machine = machine(
+                &KEY_WAIT_LOOP,
+                Quirks {
+                    key_wait_press,
+                    ..Quirks::default()
+                },
+            );
+            machine.set_keys(1 << 7);
+            for _ in 0..3 {
+                run_frame(&mut machine, 1 << 7, 10);
+            }
+            assert_eq!(machine.registers[1], 0);
+            assert!(machine.waiting());
+        }
+    }
+
+    #[test]
+    fn reset_clears_the_machine_and_keeps_the_rom() {
+        // V3 = 5, I = the sprite at 0x210, both timers = 5, draw it, call a loop.
+        let rom = [
+            0x63, 0x05, 0xA2, 0x10, 0xF3, 0x15, 0xF3, 0x18, 0xD0, 0x01, 0x22, 0x0E, 0x00, 0x00,
+            0x12, 0x0E, 0xFF,
+        ];
+        let mut machine = machine(&rom, Quirks::default());
+        run_frame(&mut machine, 1 << 4, 10);
+        assert!(machine.display.iter().any(|&pixel| pixel != 0));
+        assert_ne!(machine.sp, cpu_const::STACK_POINT_START);
+        assert_ne!((machine.delay_timer, machine.sound_timer), (0, 0));
+
+        machine.reset(&rom).unwrap();
+        assert_eq!(machine.registers, [0; 16]);
+        assert_eq!((machine.i, machine.pc), (0, cpu_const::PC_START));
+        assert_eq!(machine.sp, cpu_const::STACK_POINT_START);
+        assert_eq!((machine.delay_timer, machine.sound_timer), (0, 0));
+        assert!(machine.display.iter().all(|&pixel| pixel == 0));
+        assert_eq!(machine.keys(), 0);
+        let start = cpu_const::PC_START;
+        assert_eq!(machine.memory[start..start + rom.len()], rom);
+        assert!(machine.memory[start + rom.len()..]
+            .iter()
+            .all(|&byte| byte == 0));
+    }
+
+    #[test]
+    fn reset_rejects_a_rom_larger_than_memory() {
+        let mut machine = Machine::new(Quirks::default());
+        let rom = [0; MAX_ROM_SIZE + 1];
+        assert_eq!(machine.reset(&rom), Err(RomTooLarge(MAX_ROM_SIZE + 1)));
+        assert!(machine.reset(&rom[..MAX_ROM_SIZE]).is_ok());
+    }
+
+    #[test]
+    fn unknown_instruction_halts_the_machine() {
+        let mut machine = machine(&[0x60, 0x01, 0xE0, 0x00], Quirks::default());
+        let fault = machine.step_frame(10, &mut || 0).unwrap_err();
+        assert_eq!(fault.kind, FaultKind::UnknownOpcode);
+        assert_eq!((fault.address, fault.opcode), (0x202, 0xE000));
+        assert_eq!(machine.pc, 0x202);
+        assert_eq!(machine.cycle(&mut || 0), Err(fault));
+        machine.reset(&[0x12, 0x00]).unwrap();
+        assert!(machine.fault().is_none());
+    }
+
+    #[test]
+    fn running_off_the_end_of_memory_halts() {
+        let mut machine = machine(&[0x1F, 0xFF], Quirks::default());
+        let fault = machine.step_frame(10, &mut || 0).unwrap_err();
+        assert_eq!(fault.kind, FaultKind::OutOfMemory);
+        assert_eq!(fault.address, 0xFFF);
+    }
+
+    #[test]
+    fn stores_past_the_end_of_memory_halt() {
+        // I = 0xFFE, then FX55 for V0..VF.
+        let mut machine = machine(&[0xAF, 0xFE, 0xFF, 0x55], Quirks::default());
+        let fault = machine.step_frame(10, &mut || 0).unwrap_err();
+        assert_eq!(fault.kind, FaultKind::OutOfMemory);
+        assert_eq!(fault.address, 0x202);
+    }
+
+    #[test]
+    fn return_with_an_empty_stack_halts() {
+        let mut machine = machine(&[0x00, 0xEE], Quirks::default());
+        let fault = machine.step_frame(10, &mut || 0).unwrap_err();
+        assert_eq!(fault.kind, FaultKind::StackUnderflow);
+    }
+
+    /// Runs `cycles` instructions of `rom` with only `quirks` switched on.
+    fn run_with(rom: &[u8], quirks: Quirks, cycles: usize) -> Machine {
+        let mut machine = machine(rom, quirks);
+        for _ in 0..cycles {
+            machine.cycle(&mut || 0).unwrap();
+        }
+        machine
+    }
+
+    #[test]
+    fn shift_quirk_shifts_vx_in_place() {
+        // V0 = 0x81, V1 = 0x04, V0 = V1 >> 1 (or V0 >> 1 with the quirk).
+        let rom = [0x60, 0x81, 0x61, 0x04, 0x80, 0x16];
+        let machine = run_with(&rom, Quirks::default(), 3);
+        assert_eq!((machine.registers[0], machine.registers[0xF]), (0x02, 0));
+        let shift = Quirks {
+            shift: true,
+            ..Quirks::default()
+        };
+        let machine = run_with(&rom, shift, 3);
+        assert_eq!((machine.registers[0], machine.registers[0xF]), (0x40, 1));
+    }
+
+    #[test]
+    fn logic_quirk_resets_vf() {
+        // VF = 1, V0 |= V1.
+        let rom = [0x6F, 0x01, 0x80, 0x11];
+        assert_eq!(run_with(&rom, Quirks::default(), 2).registers[0xF], 1);
+        let logic = Quirks {
+            logic: true,
+            ..Quirks::default()
+        };
+        assert_eq!(run_with(&rom, logic, 2).registers[0xF], 0);
+    }
+
+    #[test]
+    fn memory_quirks_move_i_less() {
+        // I = 0x300, store V0..V2.
+        let rom = [0xA3, 0x00, 0xF2, 0x55];
+        assert_eq!(run_with(&rom, Quirks::default(), 2).i, 0x303);
+        let by_x = Quirks {
+            memory_increment_by_x: true,
+            ..Quirks::default()
+        };
+        assert_eq!(run_with(&rom, by_x, 2).i, 0x302);
+        let unchanged = Quirks {
+            memory_leave_i_unchanged: true,
+            ..Quirks::default()
+        };
+        assert_eq!(run_with(&rom, unchanged, 2).i, 0x300);
+    }
+
+    #[test]
+    fn jump_quirk_adds_vx() {
+        // V0 = 2, V3 = 4, jump to 0x300 plus V0 (or V3 with the quirk).
+        let rom = [0x60, 0x02, 0x63, 0x04, 0xB3, 0x00];
+        assert_eq!(run_with(&rom, Quirks::default(), 3).pc, 0x302);
+        let jump = Quirks {
+            jump: true,
+            ..Quirks::default()
+        };
+        assert_eq!(run_with(&rom, jump, 3).pc, 0x304);
+    }
+
+    #[test]
+    fn sprites_are_clipped_unless_wrap_is_on() {
+        // V0 = 62, I = the 0xFF byte at 0x208, draw one row at (62, 0).
+        let rom = [0x60, 0x3E, 0xA2, 0x08, 0xD0, 0x11, 0x12, 0x06, 0xFF];
+        let machine = run_with(&rom, Quirks::default(), 3);
+        assert_eq!(
+            machine.display[..WIDTH].iter().filter(|&&p| p == 1).count(),
+            2
+        );
+        assert_eq!(machine.display[0], 0);
+        let wrap = Quirks {
+            wrap: true,
+            ..Quirks::default()
+        };
+        let machine = run_with(&rom, wrap, 3);
+        assert_eq!(
+            machine.display[..WIDTH].iter().filter(|&&p| p == 1).count(),
+            8
+        );
+        assert_eq!(machine.display[0], 1);
+    }
+
+    #[test]
+    fn vblank_quirk_draws_once_per_frame() {
+        // I = the 0x80 byte at 0x208, draw at (V0, V0), V0 += 1, loop.
+        let rom = [0xA2, 0x08, 0xD0, 0x01, 0x70, 0x01, 0x12, 0x02, 0x80];
+        let drawn = |machine: &Machine| machine.display.iter().filter(|&&p| p == 1).count();
+        let mut modern = machine(&rom, Quirks::default());
+        modern.step_frame(9, &mut || 0).unwrap();
+        assert_eq!(drawn(&modern), 3);
+        let vblank = Quirks {
+            vblank: true,
+            ..Quirks::default()
+        };
+        let mut vip = machine(&rom, vblank);
+        vip.step_frame(9, &mut || 0).unwrap();
+        assert_eq!(drawn(&vip), 1);
+        vip.step_frame(9, &mut || 0).unwrap();
+        assert_eq!(drawn(&vip), 2);
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn quirks_round_trip_through_names_and_states() {
+        use alloc::string::ToString;
+
+        let quirks: Quirks = "wrap, vblank,key-wait-press".parse().unwrap();
+        assert_eq!(quirks.to_string(), "key-wait-press,wrap,vblank");
+        assert_eq!(Quirks::from_bits(quirks.bits()), quirks);
+        assert!("keyWaitPress".parse::<Quirks>().is_err());
+
+        let mut machine = machine(&[0x12, 0x00], quirks);
+        let state = machine.save_state();
+        machine.quirks = Quirks::default();
+        machine.load_state(&state).unwrap();
+        assert_eq!(machine.quirks, quirks);
+    }
+}
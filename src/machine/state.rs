@@ -0,0 +1,140 @@
+use core::fmt;
+
+use super::{Machine, Quirks, HEIGHT, MEMORY_SIZE, WIDTH};
+
+const MAGIC: &[u8; 4] = b"C8S1";
+
+/// Size in bytes of a state saved with `Machine::save_state`.
+pub const STATE_SIZE: usize = MAGIC.len()
+    + MEMORY_SIZE
+    + 16 // registers
+    + 2 * 3 // I, PC, SP
+    + 2 // delay and sound timers
+    + 1 // quirks
+    + WIDTH * HEIGHT
+    + 2 * 2 // keys, last keys
+    + 2 // awaited key, waiting and drawn flags
+    + 2 * 2 // last PC, last opcode
+    + 8; // cycles
+
+#[derive(Debug)]
+pub enum StateError {
+    WrongSize {
+        size: usize,
+        expected: usize,
+    },
+    NotAState,
+    /// PC or SP point outside of memory.
+    Corrupt,
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::WrongSize { size, expected } => write!(
+                f,
+                "saved state is {} bytes, expected {} bytes",
+                size, expected
+            ),
+            StateError::NotAState => write!(f, "not a saved CHIP-8 state"),
+            StateError::Corrupt => write!(f, "saved state is corrupt"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for StateError {}
+
+fn put(state: &mut [u8], pos: &mut usize, bytes: &[u8]) {
+    state[*pos..*pos + bytes.len()].copy_from_slice(bytes);
+    *pos += bytes.len();
+}
+
+fn take<'a>(state: &'a [u8], pos: &mut usize, len: usize) -> &'a [u8] {
+    *pos += len;
+    &state[*pos - len..*pos]
+}
+
+fn take_u16(state: &[u8], pos: &mut usize) -> u16 {
+    let bytes = take(state, pos, 2);
+    u16::from_le_bytes([bytes[0], bytes[1]])
+}
+
+impl Machine {
+    /// Everything needed to resume exactly where the machine is now, apart from the
+    /// host's random source.
+    pub fn save_state(&self) -> [u8; STATE_SIZE] {
+        let mut state = [0; STATE_SIZE];
+        let pos = &mut 0;
+        put(&mut state, pos, MAGIC);
+        put(&mut state, pos, &self.memory);
+        put(&mut state, pos, &self.registers);
+        put(&mut state, pos, &self.i.to_le_bytes());
+        put(&mut state, pos, &(self.pc as u16).to_le_bytes());
+        put(&mut state, pos, &(self.sp as u16).to_le_bytes());
+        put(&mut state, pos, &[self.delay_timer, self.sound_timer]);
+        put(&mut state, pos, &[self.quirks.bits()]);
+        put(&mut state, pos, &self.display);
+        put(&mut state, pos, &self.keys.to_le_bytes());
+        put(&mut state, pos, &self.last_keys.to_le_bytes());
+        put(
+            &mut state,
+            pos,
+            &[
+                self.awaited_key.unwrap_or(0xFF),
+                self.wainting as u8 | (self.drawn as u8) << 1,
+            ],
+        );
+        put(&mut state, pos, &(self.last_pc as u16).to_le_bytes());
+        put(&mut state, pos, &self.whole.to_le_bytes());
+        put(&mut state, pos, &(self.cycles as u64).to_le_bytes());
+        state
+    }
+
+    /// Restores a state from `save_state`; the machine is left untouched on error.
+    pub fn load_state(&mut self, state: &[u8]) -> Result<(), StateError> {
+        if state.len() != STATE_SIZE {
+            return Err(StateError::WrongSize {
+                size: state.len(),
+                expected: STATE_SIZE,
+            });
+        }
+        let pos = &mut 0;
+        if take(state, pos, MAGIC.len()) != MAGIC {
+            return Err(StateError::NotAState);
+        }
+        let memory = take(state, pos, MEMORY_SIZE);
+        let registers = take(state, pos, 16);
+        let i = take_u16(state, pos);
+        let pc = take_u16(state, pos) as usize;
+        let sp = take_u16(state, pos) as usize;
+        if pc + 1 >= MEMORY_SIZE || sp >= MEMORY_SIZE {
+            return Err(StateError::Corrupt);
+        }
+        self.memory.copy_from_slice(memory);
+        self.registers.copy_from_slice(registers);
+        self.i = i;
+        self.pc = pc;
+        self.sp = sp;
+        let timers = take(state, pos, 2);
+        self.delay_timer = timers[0];
+        self.sound_timer = timers[1];
+        self.quirks = Quirks::from_bits(take(state, pos, 1)[0]);
+        self.display
+            .copy_from_slice(take(state, pos, WIDTH * HEIGHT));
+        self.keys = take_u16(state, pos);
+        self.last_keys = take_u16(state, pos);
+        let wait = take(state, pos, 2);
+        self.awaited_key = Some(wait[0]).filter(|&key| key < 16);
+        self.wainting = wait[1] & 1 != 0;
+        self.drawn = wait[1] & 2 != 0;
+        self.last_pc = take_u16(state, pos) as usize;
+        self.whole = take_u16(state, pos);
+        let mut cycles = [0; 8];
+        cycles.copy_from_slice(take(state, pos, 8));
+        self.cycles = u64::from_le_bytes(cycles) as usize;
+        self.redraw = true;
+        self.fault = None;
+        Ok(())
+    }
+}
@@ -1,13 +1,18 @@
-mod chip8;
-
+use chip_8::options::Chip8Options;
+use chip_8::Chip8;
 use clap::Parser;
 
 fn main() {
-    let mut options = chip8::options::Chip8Options::parse();
-    options.build();
-    let mut cpu = chip8::Chip8::new_with_rom(options);
+    let mut options = Chip8Options::parse();
+    if let Err(err) = options.build() {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+    let mut cpu = Chip8::new_with_rom(options).unwrap_or_else(|err| {
+        eprintln!("error: unable to start the frontend: {}", err);
+        std::process::exit(1);
+    });
     while cpu.running {
-        cpu.cycle();
-        // std::thread::sleep(Duration::from_millis(1));
+        cpu.frame();
     }
 }
@@ -0,0 +1,88 @@
+//! JavaScript bindings, packaged by the `web/` crate (`wasm-pack build web --target web`)
+//! and tested with `wasm-pack test --node web`.
+//! `web/index.html` shows how to drive them.
+
+use alloc::string::ToString;
+use alloc::vec::Vec;
+
+use wasm_bindgen::prelude::*;
+
+use crate::emulator;
+use crate::machine::{self, Quirks};
+
+#[wasm_bindgen]
+pub struct Emulator {
+    emulator: emulator::Emulator,
+}
+
+#[wasm_bindgen]
+impl Emulator {
+    /// An emulator with no ROM loaded; `seed` drives CXNN, so pass something random
+    /// like `Math.random() * 2 ** 32` unless runs should be repeatable.
+    #[wasm_bindgen(constructor)]
+    pub fn new(seed: u32) -> Emulator {
+        Emulator {
+            emulator: emulator::Emulator::new(Quirks::default(), seed as u64),
+        }
+    }
+
+    /// Loads a ROM and resets the machine; throws if it is empty or too large.
+    #[wasm_bindgen(js_name = loadRom)]
+    pub fn load_rom(&mut self, rom: &[u8]) -> Result<(), JsError> {
+        self.emulator
+            .load_rom(rom)
+            .map_err(|err| JsError::new(&err.to_string()))
+    }
+
+    /// Restarts the loaded ROM.
+    pub fn reset(&mut self) {
+        self.emulator.reset();
+    }
+
+    /// Instructions executed per frame.
+    #[wasm_bindgen(getter)]
+    pub fn tickrate(&self) -> u32 {
+        self.emulator.tickrate
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_tickrate(&mut self, tickrate: u32) {
+        self.emulator.tickrate = tickrate;
+    }
+
+    /// Runs one 60 Hz frame; call it from `requestAnimationFrame` or a 60 Hz timer.
+    /// Throws once the ROM runs an instruction the machine cannot execute.
+    #[wasm_bindgen(js_name = stepFrame)]
+    pub fn step_frame(&mut self) -> Result<(), JsError> {
+        self.emulator
+            .step_frame()
+            .map_err(|fault| JsError::new(&fault.to_string()))
+    }
+
+    /// Presses or releases keypad key `key` (0 to F).
+    #[wasm_bindgen(js_name = setKey)]
+    pub fn set_key(&mut self, key: u8, down: bool) {
+        self.emulator.set_key(key, down);
+    }
+
+    /// The display as a `Uint8Array`, one byte per pixel (0 or 1), row by row.
+    pub fn framebuffer(&self) -> Vec<u8> {
+        self.emulator.machine().display.to_vec()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn width(&self) -> usize {
+        machine::WIDTH
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn height(&self) -> usize {
+        machine::HEIGHT
+    }
+
+    /// Whether the beeper should sound.
+    #[wasm_bindgen(getter)]
+    pub fn sound(&self) -> bool {
+        self.emulator.machine().sound_on()
+    }
+}
@@ -0,0 +1,6 @@
+//! The `chip_8` JavaScript bindings packaged as a WebAssembly module.
+//!
+//! Build with `wasm-pack build web --target web`, then serve `web/` and open
+//! `index.html`. `chip_8` itself stays an rlib so it can be used without `std`.
+
+pub use chip_8::wasm::Emulator;
@@ -0,0 +1,24 @@
+//! Run with `wasm-pack test --node web`.
+
+#![cfg(target_arch = "wasm32")]
+
+use chip_8_web::Emulator;
+use wasm_bindgen_test::wasm_bindgen_test;
+
+#[wasm_bindgen_test]
+fn draws_a_sprite() {
+    let mut emulator = Emulator::new(1);
+    // I = 0x208, draw one row at (V0, V0), loop; the row is 11110000.
+    emulator
+        .load_rom(&[0xA2, 0x08, 0xD0, 0x01, 0x12, 0x04, 0x00, 0x00, 0xF0])
+        .unwrap();
+    emulator.step_frame().unwrap();
+    let pixels = emulator.framebuffer();
+    assert_eq!(pixels.len(), emulator.width() * emulator.height());
+    assert_eq!(&pixels[..5], &[1, 1, 1, 1, 0]);
+}
+
+#[wasm_bindgen_test]
+fn rejects_an_empty_rom() {
+    assert!(Emulator::new(1).load_rom(&[]).is_err());
+}